@@ -0,0 +1,23 @@
+use std::process::Command;
+
+// generated by `sqlx migrate build-script`
+fn main() {
+    // trigger recompilation when a new migration is added
+    println!("cargo:rerun-if-changed=migrations");
+
+    // `/version` reports the commit it was built from. CI can set GIT_HASH itself, otherwise ask git,
+    // and builds outside a checkout say "unknown".
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+    let git_hash = std::env::var("GIT_HASH").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+    });
+    let git_hash = git_hash.as_deref().map(str::trim).unwrap_or("unknown");
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+}
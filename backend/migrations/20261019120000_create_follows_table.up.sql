@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS follows
+(
+    id              serial PRIMARY KEY,
+    follower_id     INTEGER REFERENCES users ON DELETE CASCADE NOT NULL,
+    followed_id     INTEGER REFERENCES users ON DELETE CASCADE NOT NULL,
+    created_on      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT unique_follows UNIQUE(follower_id, followed_id),
+    CONSTRAINT no_self_follows CHECK (follower_id <> followed_id)
+);
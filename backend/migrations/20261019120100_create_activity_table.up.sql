@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS activity
+(
+    id          serial PRIMARY KEY,
+    user_id     INTEGER REFERENCES users ON DELETE CASCADE NOT NULL,
+    post_id     INTEGER REFERENCES posts ON DELETE CASCADE NOT NULL,
+    kind        VARCHAR(32) NOT NULL,
+    created_on  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS activity_user_created_idx ON activity (user_id, created_on DESC);
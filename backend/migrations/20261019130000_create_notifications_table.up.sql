@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS notifications
+(
+    id          serial PRIMARY KEY,
+    user_id     INTEGER REFERENCES users ON DELETE CASCADE NOT NULL,
+    kind        VARCHAR(32) NOT NULL,
+    message     TEXT NOT NULL,
+    is_read     BOOLEAN NOT NULL DEFAULT false,
+    created_on  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS notifications_user_unread_idx ON notifications (user_id, is_read);
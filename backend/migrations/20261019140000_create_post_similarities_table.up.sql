@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS post_similarities
+(
+    post_id             INTEGER REFERENCES posts ON DELETE CASCADE NOT NULL,
+    similar_post_id     INTEGER REFERENCES posts ON DELETE CASCADE NOT NULL,
+    score               DOUBLE PRECISION NOT NULL,
+    refreshed_on        TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (post_id, similar_post_id)
+);
@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_banned BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE users SET is_banned = true
+WHERE id IN (
+    SELECT user_id FROM bans
+    WHERE lifted_on IS NULL AND (expires_on IS NULL OR expires_on > NOW())
+);
+
+DROP TABLE IF EXISTS bans;
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS bans
+(
+    id          serial PRIMARY KEY,
+    user_id     INTEGER REFERENCES users ON DELETE CASCADE NOT NULL,
+    issued_by   INTEGER REFERENCES users ON DELETE SET NULL,
+    reason      TEXT NOT NULL,
+    started_on  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_on  TIMESTAMPTZ,
+    lifted_on   TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS bans_user_idx ON bans (user_id);
+
+-- Carry over anyone who was banned with the old flag, then let bans be the only source of truth
+INSERT INTO bans (user_id, reason)
+SELECT id, 'Banned before ban reasons were recorded' FROM users WHERE is_banned = true;
+
+ALTER TABLE users DROP COLUMN IF EXISTS is_banned;
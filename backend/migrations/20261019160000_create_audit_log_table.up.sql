@@ -0,0 +1,23 @@
+-- actor_id deliberately has no foreign key, entries have to outlive the users they mention
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id              serial PRIMARY KEY,
+    actor_id        INTEGER,
+    actor_email     VARCHAR(255) NOT NULL,
+    action          VARCHAR(64) NOT NULL,
+    target          TEXT NOT NULL,
+    payload         JSONB NOT NULL DEFAULT '{}',
+    created_on      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_created_idx ON audit_log (created_on DESC);
+
+CREATE OR REPLACE FUNCTION audit_log_is_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION audit_log_is_append_only();
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS admins
+(
+    id                 serial PRIMARY KEY,
+    admin_user_id      integer REFERENCES users ON DELETE CASCADE UNIQUE
+);
+
+INSERT INTO admins (admin_user_id)
+SELECT id FROM users WHERE role IN ('admin', 'owner') ORDER BY role = 'owner' DESC, id;
+
+ALTER TABLE users DROP COLUMN IF EXISTS role;
@@ -0,0 +1,8 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS role VARCHAR(32) NOT NULL DEFAULT 'user'
+    CONSTRAINT valid_role CHECK (role IN ('user', 'moderator', 'admin', 'owner'));
+
+-- Everyone who was an admin stays one, and the very first admin becomes the owner
+UPDATE users SET role = 'admin' WHERE id IN (SELECT admin_user_id FROM admins);
+UPDATE users SET role = 'owner' WHERE id = (SELECT admin_user_id FROM admins ORDER BY id LIMIT 1);
+
+DROP TABLE IF EXISTS admins;
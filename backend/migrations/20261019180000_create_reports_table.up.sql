@@ -0,0 +1,16 @@
+-- target_id points at posts or users depending on target_kind, so it can't have a foreign key
+CREATE TABLE IF NOT EXISTS reports
+(
+    id                  serial PRIMARY KEY,
+    reporter_id         INTEGER REFERENCES users ON DELETE SET NULL,
+    target_kind         VARCHAR(32) NOT NULL,
+    target_id           INTEGER NOT NULL,
+    reason              TEXT NOT NULL,
+    created_on          TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_by         INTEGER REFERENCES users ON DELETE SET NULL,
+    resolved_on         TIMESTAMPTZ,
+    resolution          VARCHAR(32),
+    resolution_note     TEXT
+);
+
+CREATE INDEX IF NOT EXISTS reports_open_idx ON reports (resolved_on, created_on);
@@ -0,0 +1,2 @@
+-- Existing users get the migration time as their signup date, we never recorded the real one
+ALTER TABLE users ADD COLUMN IF NOT EXISTS created_on TIMESTAMPTZ NOT NULL DEFAULT NOW();
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS nasa_fetches;
@@ -0,0 +1,9 @@
+-- One row per call we make to the APOD API, cache hits never reach NASA so they aren't recorded
+CREATE TABLE IF NOT EXISTS nasa_fetches
+(
+    id              serial PRIMARY KEY,
+    query_string    VARCHAR(255) NOT NULL,
+    succeeded       BOOLEAN NOT NULL,
+    error           TEXT,
+    created_on      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,16 @@
+-- Every version a post has had, newest has the highest id. The version a post was ingested with is
+-- only saved here the first time someone edits it.
+CREATE TABLE IF NOT EXISTS post_revisions
+(
+    id              serial PRIMARY KEY,
+    post_id         INTEGER REFERENCES posts ON DELETE CASCADE NOT NULL,
+    editor_id       INTEGER REFERENCES users ON DELETE SET NULL,
+    source          VARCHAR(32) NOT NULL,
+    title           VARCHAR(255) NOT NULL,
+    explanation     TEXT NOT NULL,
+    img_url         TEXT NOT NULL,
+    apod_date       TEXT NOT NULL,
+    created_on      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS post_revisions_post_idx ON post_revisions (post_id, id DESC);
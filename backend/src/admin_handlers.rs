@@ -1,52 +1,407 @@
-use axum::extract::State;
-use axum::response::Response;
-use axum::Form;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Form, Json};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use http::header::CONTENT_DISPOSITION;
 use hyper::Body;
+use tera::Context;
+use tracing::error;
 
-use crate::db::Store;
+use crate::config::Config;
+use crate::db::{Store, AUDIT_PAGE_SIZE, STATS_DAYS, USER_PAGE_SIZE};
 use crate::error::AppError;
-use crate::handlers::create_response_path;
-use crate::models::user::UserEmail;
+use crate::flash::{redirect_with_flash, Flash, IncomingFlash};
+use crate::handlers::{create_redirect_to, require_outranks, require_permission};
+use crate::metrics::METRICS;
+use crate::models::audit::{AuditAction, AuditQuery};
+use crate::models::ban::CreateBan;
+use crate::models::role::{Permission, Role, SetRole};
+use crate::models::stats::SiteStats;
+use crate::models::user::{BulkAction, BulkUserAction, Claims, UserEmail, UserListQuery};
+use crate::repository::Repository;
+use crate::template::TEMPLATES;
 
 // Admin ---------------------------------------------------------------------------------------------------------------
 pub async fn ban_user(
-    State(mut am_database): State<Store>,
-    Form(email_to_ban): Form<UserEmail>,
+    State(am_database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    Form(ban): Form<CreateBan>,
 ) -> Result<Response<Body>, AppError> {
-    am_database.ban_user_by_email(email_to_ban.email).await?;
-    let response = create_response_path();
-    Ok(response)
+    let actor_role =
+        require_permission(am_database.as_ref(), &claims, Permission::BanUsers).await?;
+    require_outranks(am_database.as_ref(), &claims, actor_role, &ban.email).await?;
+    let message = format!("{} is banned", ban.email);
+
+    let expires_on = parse_ban_expiry(&ban.expires_on)?;
+    let reason = if ban.reason.trim().is_empty() {
+        "No reason given".to_string()
+    } else {
+        ban.reason.trim().to_string()
+    };
+    am_database
+        .ban_user_by_email(
+            claims.email,
+            Some(actor_role),
+            ban.email,
+            reason,
+            expires_on,
+        )
+        .await?;
+    METRICS.record_bans(1);
+    redirect_with_flash("/", Flash::success(message), &config)
 }
 
 pub async fn unban_user(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
     Form(email_to_unban): Form<UserEmail>,
 ) -> Result<Response<Body>, AppError> {
+    let actor_role =
+        require_permission(am_database.as_ref(), &claims, Permission::BanUsers).await?;
+    require_outranks(
+        am_database.as_ref(),
+        &claims,
+        actor_role,
+        &email_to_unban.email,
+    )
+    .await?;
+    let message = format!("{} is no longer banned", email_to_unban.email);
+
     am_database
-        .unban_user_by_email(email_to_unban.email)
+        .unban_user_by_email(claims.email, Some(actor_role), email_to_unban.email)
         .await?;
-    let response = create_response_path();
-    Ok(response)
+    redirect_with_flash("/", Flash::success(message), &config)
 }
 
 pub async fn promote_admin(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
     Form(email_to_admin): Form<UserEmail>,
 ) -> Result<Response<Body>, AppError> {
+    let actor_role =
+        require_permission(am_database.as_ref(), &claims, Permission::ManageRoles).await?;
+    require_outranks(
+        am_database.as_ref(),
+        &claims,
+        actor_role,
+        &email_to_admin.email,
+    )
+    .await?;
+    if actor_role <= Role::Admin {
+        return Err(AppError::CannotActOnUser);
+    }
+    let message = format!("{} is now an admin", email_to_admin.email);
+
     am_database
-        .promote_admin_by_email(email_to_admin.email)
+        .promote_admin_by_email(claims.email, Some(actor_role), email_to_admin.email)
         .await?;
-    let response = create_response_path();
-    Ok(response)
+    redirect_with_flash("/", Flash::success(message), &config)
 }
 
 pub async fn demote_admin(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
     Form(email_to_admin): Form<UserEmail>,
 ) -> Result<Response<Body>, AppError> {
+    let actor_role =
+        require_permission(am_database.as_ref(), &claims, Permission::ManageRoles).await?;
+    require_outranks(
+        am_database.as_ref(),
+        &claims,
+        actor_role,
+        &email_to_admin.email,
+    )
+    .await?;
+    let message = format!("{} is no longer an admin", email_to_admin.email);
+
     am_database
-        .demote_admin_by_email(email_to_admin.email)
+        .demote_admin_by_email(claims.email, Some(actor_role), email_to_admin.email)
+        .await?;
+    redirect_with_flash("/", Flash::success(message), &config)
+}
+
+pub async fn set_role(
+    State(am_database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    Form(new_role): Form<SetRole>,
+) -> Result<Response<Body>, AppError> {
+    let actor_role =
+        require_permission(am_database.as_ref(), &claims, Permission::ManageRoles).await?;
+    require_outranks(am_database.as_ref(), &claims, actor_role, &new_role.email).await?;
+    if new_role.role >= actor_role {
+        return Err(AppError::CannotActOnUser);
+    }
+    let message = format!("{} is now {}", new_role.email, new_role.role);
+
+    am_database
+        .set_user_role_by_email(
+            claims.email,
+            Some(actor_role),
+            new_role.email,
+            new_role.role,
+        )
+        .await?;
+    redirect_with_flash("/", Flash::success(message), &config)
+}
+
+pub async fn ban_history(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Query(user): Query<UserEmail>,
+) -> Result<Html<String>, AppError> {
+    require_permission(&am_database, &claims, Permission::BanUsers).await?;
+
+    let bans = am_database
+        .get_ban_history_by_email(user.email.clone())
+        .await?;
+
+    let mut context = Context::new();
+    context.insert("email", &user.email);
+    context.insert("bans", &bans);
+
+    let rendered = TEMPLATES
+        .render("ban_history.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok(Html(rendered))
+}
+
+pub async fn audit_log_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Query(query): Query<AuditQuery>,
+) -> Result<Html<String>, AppError> {
+    require_permission(&am_database, &claims, Permission::ViewAuditLog).await?;
+
+    let entries = am_database
+        .get_audit_log(&query, Some(AUDIT_PAGE_SIZE))
         .await?;
-    let response = create_response_path();
-    Ok(response)
+    let actions: Vec<String> = AuditAction::ALL
+        .iter()
+        .map(|action| action.to_string())
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("entries", &entries);
+    context.insert("actions", &actions);
+    context.insert("actor", &query.actor().unwrap_or_default());
+    context.insert("action", &query.action().unwrap_or_default());
+    context.insert("target", &query.target().unwrap_or_default());
+    context.insert("page", &query.page.unwrap_or(0).max(0));
+    context.insert("has_next_page", &(entries.len() as i64 == AUDIT_PAGE_SIZE));
+
+    let rendered = TEMPLATES
+        .render("audit.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok(Html(rendered))
+}
+
+/// Every entry matching the filters as a JSON download, ignoring pagination
+pub async fn export_audit_log(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, AppError> {
+    require_permission(&am_database, &claims, Permission::ViewAuditLog).await?;
+
+    let entries = am_database.get_audit_log(&query, None).await?;
+
+    Ok((
+        [(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"audit_log.json\"",
+        )],
+        Json(entries),
+    )
+        .into_response())
+}
+
+pub async fn dashboard_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+) -> Result<Html<String>, AppError> {
+    require_permission(&am_database, &claims, Permission::ViewDashboard).await?;
+
+    let stats = am_database.get_site_stats(STATS_DAYS).await?;
+    let busiest_signup_day = stats
+        .daily_signups
+        .iter()
+        .map(|day| day.count)
+        .max()
+        .unwrap_or(0);
+    let busiest_vote_day = stats
+        .daily_votes
+        .iter()
+        .map(|day| day.count)
+        .max()
+        .unwrap_or(0);
+
+    let mut context = Context::new();
+    context.insert("stats", &stats);
+    context.insert("days", &STATS_DAYS);
+    context.insert("busiest_signup_day", &busiest_signup_day.max(1));
+    context.insert("busiest_vote_day", &busiest_vote_day.max(1));
+
+    let rendered = TEMPLATES
+        .render("dashboard.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok(Html(rendered))
+}
+
+/// The same numbers as the dashboard page, for scripts
+pub async fn dashboard_json(
+    State(am_database): State<Store>,
+    claims: Claims,
+) -> Result<Json<SiteStats>, AppError> {
+    require_permission(&am_database, &claims, Permission::ViewDashboard).await?;
+
+    let stats = am_database.get_site_stats(STATS_DAYS).await?;
+    Ok(Json(stats))
+}
+
+pub async fn user_management_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Query(query): Query<UserListQuery>,
+    flash: IncomingFlash,
+) -> Result<(IncomingFlash, Html<String>), AppError> {
+    let role = require_permission(&am_database, &claims, Permission::ManageUsers).await?;
+
+    let users = am_database.get_users(&query, USER_PAGE_SIZE).await?;
+    let permissions: Vec<String> = role
+        .permissions()
+        .iter()
+        .map(|permission| permission.to_string())
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("flash", &flash.0);
+    context.insert("users", &users);
+    context.insert("role", &role);
+    context.insert("permissions", &permissions);
+    context.insert("search", &query.search().unwrap_or_default());
+    context.insert("page", &query.page.unwrap_or(0).max(0));
+    context.insert("has_next_page", &(users.len() as i64 == USER_PAGE_SIZE));
+
+    let rendered = TEMPLATES
+        .render("users.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok((flash, Html(rendered)))
+}
+
+/// Shows a confirmation page first, the action only runs once that page is submitted. Each action
+/// needs the same permission as doing it to one user, and the current user has to outrank everyone.
+pub async fn bulk_user_action(
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    let actor_role =
+        require_permission(am_database.as_ref(), &claims, Permission::ManageUsers).await?;
+    let mut bulk = BulkUserAction::from_pairs(pairs)?;
+
+    if !bulk.action.is_allowed_for(actor_role) {
+        return Err(AppError::MissingPermission);
+    }
+    for email in &bulk.emails {
+        require_outranks(am_database.as_ref(), &claims, actor_role, email).await?;
+    }
+
+    if !bulk.confirmed {
+        let mut context = Context::new();
+        context.insert("bulk", &bulk);
+
+        let rendered = TEMPLATES
+            .render("users_confirm.html", &context)
+            .unwrap_or_else(|err| {
+                error!("Template rendering error: {}", err);
+                panic!()
+            });
+        return Ok(Html(rendered).into_response());
+    }
+
+    if bulk.action == BulkAction::Ban && bulk.reason.is_empty() {
+        bulk.reason = "No reason given".to_string();
+    }
+    am_database.bulk_update_users(claims.email, &bulk).await?;
+    if bulk.action == BulkAction::Ban {
+        METRICS.record_bans(bulk.emails.len() as u64);
+    }
+
+    Ok(create_redirect_to("/users")?.into_response())
+}
+
+/// Bans end at the start of the given `YYYY-MM-DD` day (UTC), a blank date means they never end
+pub fn parse_ban_expiry(expires_on: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+    if expires_on.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let date = NaiveDate::parse_from_str(expires_on.trim(), "%Y-%m-%d")
+        .map_err(|_| AppError::InvalidBanExpiry)?;
+    let expires_on = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+
+    if expires_on <= Utc::now() {
+        return Err(AppError::InvalidBanExpiry);
+    }
+
+    Ok(Some(expires_on))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn a_blank_expiry_never_ends() {
+        assert_eq!(parse_ban_expiry("").unwrap(), None);
+        assert_eq!(parse_ban_expiry("   ").unwrap(), None);
+    }
+
+    #[test]
+    fn a_ban_ends_at_the_start_of_its_day() {
+        let tomorrow = (Utc::now() + Duration::days(1)).date_naive();
+        let expires_on = parse_ban_expiry(&format!(" {} ", tomorrow.format("%Y-%m-%d")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            expires_on,
+            Utc.from_utc_datetime(&tomorrow.and_hms_opt(0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn past_and_malformed_dates_are_refused() {
+        let today = Utc::now().date_naive().format("%Y-%m-%d").to_string();
+        for expires_on in ["2020-01-01", today.as_str(), "next week", "2030-13-01"] {
+            assert!(
+                matches!(
+                    parse_ban_expiry(expires_on),
+                    Err(AppError::InvalidBanExpiry)
+                ),
+                "{}",
+                expires_on
+            );
+        }
+    }
 }
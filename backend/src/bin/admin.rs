@@ -0,0 +1,211 @@
+//! Admin tasks from the command line, for setting up the first owner without touching SQL.
+//!
+//! Run from `./backend` with `cargo run --bin admin -- <command>`, it loads the same configuration
+//! as the server does.
+
+use std::process::exit;
+
+use backend::admin_handlers::parse_ban_expiry;
+use backend::config::Config;
+use backend::db::{new_pool, run_migrations, Store};
+use backend::error::AppError;
+use backend::models::role::Role;
+use backend::models::seed::Fixture;
+use backend::models::user::UserSignup;
+use backend::seed::{demo_fixture, seed};
+use backend::user_handlers::hash_password;
+use dotenvy::dotenv;
+
+/// Who the audit log says did it
+const CLI_ACTOR: &str = "admin-cli";
+
+const USAGE: &str = "Usage: admin <command>
+
+Commands:
+  create-admin --email EMAIL [--password PASSWORD]
+      Make EMAIL an owner, creating the account with PASSWORD if it doesn't exist yet
+  promote --email EMAIL
+      Make EMAIL an admin
+  demote --email EMAIL
+      Make EMAIL a regular user again
+  ban --email EMAIL [--reason REASON] [--until YYYY-MM-DD]
+      Ban EMAIL, forever unless --until is given
+  list-admins
+      List everyone with admin rights
+  migrate
+      Apply any migrations the database doesn't have yet
+  seed [--file FIXTURE]
+      Load demo users, posts and votes, from FIXTURE instead of the bundled tests/fixtures/demo.json
+      if given. Anything already in the database is skipped";
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(command) = args.first() else {
+        eprintln!("{}", USAGE);
+        exit(2);
+    };
+    let flag = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+    let email = || {
+        flag("--email").unwrap_or_else(|| {
+            eprintln!("{} needs --email\n\n{}", command, USAGE);
+            exit(2);
+        })
+    };
+
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    let store = Store::with_pool(new_pool(&config).await);
+    let result = match command.as_str() {
+        "create-admin" => create_admin(&store, &config, email(), flag("--password")).await,
+        "promote" => promote(&store, email()).await,
+        "demote" => demote(&store, email()).await,
+        "ban" => {
+            let reason = flag("--reason").unwrap_or_else(|| "No reason given".to_string());
+            ban(&store, email(), reason, flag("--until").unwrap_or_default()).await
+        }
+        "list-admins" => list_admins(&store).await,
+        "migrate" => migrate(&store).await,
+        "seed" => seed_demo_data(&store, &config, flag("--file")).await,
+        _ => {
+            eprintln!("Unknown command {}\n\n{}", command, USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("{} failed: {:?}", command, err);
+        exit(1);
+    }
+}
+
+async fn create_admin(
+    store: &Store,
+    config: &Config,
+    email: String,
+    password: Option<String>,
+) -> Result<(), AppError> {
+    if store.get_user(&email).await.is_err() {
+        let Some(password) = password else {
+            eprintln!(
+                "{} doesn't have an account yet, pass --password to create one",
+                email
+            );
+            exit(2);
+        };
+        let _ = store
+            .create_user(UserSignup {
+                email: email.clone(),
+                password: hash_password(&password, &config.salt)?,
+                confirm_password: String::new(),
+            })
+            .await?;
+        println!("Created an account for {}", email);
+    }
+
+    store
+        .set_user_role_by_email(CLI_ACTOR.to_string(), None, email.clone(), Role::Owner)
+        .await?;
+
+    println!("{} is now an owner", email);
+    Ok(())
+}
+
+async fn promote(store: &Store, email: String) -> Result<(), AppError> {
+    store
+        .promote_admin_by_email(CLI_ACTOR.to_string(), None, email.clone())
+        .await?;
+
+    println!("{} is now an admin", email);
+    Ok(())
+}
+
+async fn demote(store: &Store, email: String) -> Result<(), AppError> {
+    store
+        .demote_admin_by_email(CLI_ACTOR.to_string(), None, email.clone())
+        .await?;
+
+    println!("{} is no longer an admin", email);
+    Ok(())
+}
+
+async fn ban(store: &Store, email: String, reason: String, until: String) -> Result<(), AppError> {
+    let expires_on = parse_ban_expiry(&until)?;
+    let new_ban = store
+        .ban_user_by_email(
+            CLI_ACTOR.to_string(),
+            None,
+            email.clone(),
+            reason,
+            expires_on,
+        )
+        .await?;
+
+    match new_ban.expires_on {
+        Some(expires_on) => println!(
+            "{} is banned until {}",
+            email,
+            expires_on.format("%Y-%m-%d")
+        ),
+        None => println!("{} is banned", email),
+    }
+    Ok(())
+}
+
+async fn list_admins(store: &Store) -> Result<(), AppError> {
+    let admins = store.get_admins().await?;
+    if admins.is_empty() {
+        println!("Nobody has admin rights yet, use create-admin to add an owner");
+    }
+    for admin in admins {
+        let last_login = admin
+            .last_login_on
+            .map(|last_login| last_login.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string());
+        println!(
+            "{:<40} {:<8} last login {}{}",
+            admin.email,
+            admin.role.to_string(),
+            last_login,
+            if admin.is_banned { " (banned)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+async fn migrate(store: &Store) -> Result<(), AppError> {
+    run_migrations(&store.conn_pool).await?;
+    println!("Migrations are up to date");
+    Ok(())
+}
+
+async fn seed_demo_data(
+    store: &Store,
+    config: &Config,
+    file: Option<String>,
+) -> Result<(), AppError> {
+    let fixture = match file {
+        Some(file) => {
+            let contents =
+                std::fs::read_to_string(&file).map_err(|err| AppError::Any(err.into()))?;
+            serde_json::from_str::<Fixture>(&contents).map_err(|err| AppError::Any(err.into()))?
+        }
+        None => demo_fixture()?,
+    };
+
+    let summary = seed(store, config, fixture).await?;
+    println!(
+        "Added {} users, {} posts and {} votes",
+        summary.users, summary.posts, summary.votes
+    );
+    Ok(())
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use cookie::SameSite;
+use sha2::{Digest, Sha512};
+
+use crate::models::user::Keys;
+
+/// Where the optional TOML file is read from unless `CONFIG_FILE` says otherwise
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Everything the backend reads from its environment. Loaded once at startup and shared through the
+/// app state, so a missing key stops the server before it takes any requests.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub api_host: IpAddr,
+    pub api_port: u16,
+    pub database_url: String,
+    pub db_max_connections: u32,
+    /// Apply the embedded migrations before serving, off by default so production upgrades stay deliberate
+    pub run_migrations: bool,
+    pub jwt_secret: String,
+    /// How long a login lasts before the JWT expires
+    pub token_lifetime_hours: u64,
+    pub salt: String,
+    pub nasa_api_key: String,
+    pub nasa_base_url: String,
+    /// Scheme and host the site is reached on from outside, without a trailing slash, used for links
+    /// that leave the site such as the ones in feeds
+    pub public_base_url: String,
+    /// Only send the login cookie over HTTPS, turn on wherever the site is served over HTTPS
+    pub cookie_secure: bool,
+    pub cookie_same_site: SameSite,
+    pub initial_admin_email: Option<String>,
+    pub log_format: LogFormat,
+    /// How long shutdown waits for open requests, and then for background tasks, before giving up on them
+    pub shutdown_timeout_secs: u64,
+}
+
+/// How log lines are written to stdout
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable, for a terminal
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Every key that was missing or couldn't be parsed, not just the first one
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the TOML file at `CONFIG_FILE` (or `config.toml`) if there is one, then the environment
+    /// on top of it, so environment variables and `.env` always win. TOML keys are the lowercase
+    /// versions of the environment variable names.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut values = HashMap::new();
+        let mut problems = Vec::new();
+
+        let explicit_file = std::env::var("CONFIG_FILE").ok();
+        let file = explicit_file
+            .clone()
+            .unwrap_or_else(|| DEFAULT_CONFIG_FILE.to_string());
+        if explicit_file.is_some() || Path::new(&file).exists() {
+            match read_toml(&file) {
+                Ok(file_values) => values.extend(file_values),
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        for (key, value) in std::env::vars() {
+            values.insert(key.to_uppercase(), value);
+        }
+
+        Self::from_values(values, problems)
+    }
+
+    /// Builds the config from uppercase keys, adding to `problems` as it goes
+    pub fn from_values(
+        values: HashMap<String, String>,
+        problems: Vec<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut reader = Reader { values, problems };
+
+        let api_host = reader.required("API_HOST");
+        let api_port = reader.required("API_PORT");
+        let database_url = reader.required("DATABASE_URL");
+        let db_max_connections = reader.optional("DB_MAX_CONNECTIONS", 5);
+        let run_migrations = reader.optional("RUN_MIGRATIONS", false);
+        let jwt_secret = reader.required::<String>("JWT_SECRET");
+        let token_lifetime_hours = reader.optional("TOKEN_LIFETIME_HOURS", 8);
+        let salt = reader.required::<String>("SALT");
+        let nasa_api_key = reader.required("NASA_API_KEY");
+        let nasa_base_url = reader.optional(
+            "NASA_BASE_URL",
+            "https://api.nasa.gov/planetary/apod".to_string(),
+        );
+        let public_base_url = reader.maybe("PUBLIC_BASE_URL");
+        let cookie_secure = reader.optional("COOKIE_SECURE", false);
+        let cookie_same_site = reader.same_site("COOKIE_SAME_SITE", SameSite::Lax);
+        let initial_admin_email = reader.maybe("INITIAL_ADMIN_EMAIL");
+        let log_format = reader.optional("LOG_FORMAT", LogFormat::default());
+        let shutdown_timeout_secs = reader.optional("SHUTDOWN_TIMEOUT_SECS", 10);
+
+        if matches!(&salt, Some(salt) if salt.len() < 8) {
+            reader
+                .problems
+                .push("SALT has to be at least 8 characters long".to_string());
+        }
+        if matches!(&jwt_secret, Some(secret) if secret.is_empty()) {
+            reader
+                .problems
+                .push("JWT_SECRET can't be empty".to_string());
+        }
+        let is_http = |url: &str| url.starts_with("http://") || url.starts_with("https://");
+        if matches!(&public_base_url, Some(url) if !is_http(url)) {
+            reader
+                .problems
+                .push("PUBLIC_BASE_URL has to start with http:// or https://".to_string());
+        }
+        if db_max_connections == 0 {
+            reader
+                .problems
+                .push("DB_MAX_CONNECTIONS has to be at least 1".to_string());
+        }
+
+        if !reader.problems.is_empty() {
+            return Err(ConfigError(reader.problems));
+        }
+
+        // Every required key is `Some` if there were no problems
+        let (api_host, api_port) = (api_host.unwrap(), api_port.unwrap());
+        let public_base_url = public_base_url
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| format!("http://{}:{}", api_host, api_port));
+        Ok(Config {
+            api_host,
+            api_port,
+            database_url: database_url.unwrap(),
+            db_max_connections,
+            run_migrations,
+            jwt_secret: jwt_secret.unwrap(),
+            token_lifetime_hours,
+            salt: salt.unwrap(),
+            nasa_api_key: nasa_api_key.unwrap(),
+            nasa_base_url,
+            public_base_url,
+            cookie_secure,
+            cookie_same_site,
+            initial_admin_email,
+            log_format,
+            shutdown_timeout_secs,
+        })
+    }
+
+    pub fn keys(&self) -> Keys {
+        Keys::new(self.jwt_secret.as_bytes())
+    }
+
+    /// Signs the flash cookie. Derived from `JWT_SECRET` rather than being one more secret to set, and
+    /// hashed with a prefix so it isn't the JWT key itself.
+    pub fn cookie_key(&self) -> cookie::Key {
+        let digest = Sha512::digest(format!("flash:{}", self.jwt_secret));
+        cookie::Key::from(&digest)
+    }
+}
+
+struct Reader {
+    values: HashMap<String, String>,
+    problems: Vec<String>,
+}
+
+impl Reader {
+    fn maybe(&self, key: &str) -> Option<String> {
+        self.values
+            .get(key)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, value: String) -> Option<T> {
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                self.problems
+                    .push(format!("{} has an invalid value: {:?}", key, value));
+                None
+            }
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, key: &str) -> Option<T> {
+        match self.maybe(key) {
+            Some(value) => self.parse(key, value),
+            None => {
+                self.problems.push(format!("{} is missing", key));
+                None
+            }
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        match self.maybe(key) {
+            Some(value) => self.parse(key, value).unwrap_or(default),
+            None => default,
+        }
+    }
+
+    fn same_site(&mut self, key: &str, default: SameSite) -> SameSite {
+        match self.maybe(key).map(|value| value.to_lowercase()).as_deref() {
+            None => default,
+            Some("strict") => SameSite::Strict,
+            Some("lax") => SameSite::Lax,
+            Some("none") => SameSite::None,
+            Some(value) => {
+                self.problems.push(format!(
+                    "{} has to be strict, lax or none, not {:?}",
+                    key, value
+                ));
+                default
+            }
+        }
+    }
+}
+
+/// Flattens the top level of a TOML file into uppercase keys, the same shape as the environment
+fn read_toml(file: &str) -> Result<HashMap<String, String>, String> {
+    let contents = std::fs::read_to_string(file)
+        .map_err(|err| format!("Couldn't read config file {}: {}", file, err))?;
+    let table: toml::Table = contents
+        .parse()
+        .map_err(|err| format!("Couldn't parse config file {}: {}", file, err))?;
+
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(value) => value,
+                other => other.to_string(),
+            };
+            (key.to_uppercase(), value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        let required = [
+            ("API_HOST", "127.0.0.1"),
+            ("API_PORT", "3000"),
+            ("DATABASE_URL", "postgres://localhost/astro"),
+            ("JWT_SECRET", "secret"),
+            ("SALT", "long-enough-salt"),
+            ("NASA_API_KEY", "DEMO_KEY"),
+        ];
+        required
+            .iter()
+            .chain(pairs)
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn problems(pairs: &[(&str, &str)]) -> Vec<String> {
+        Config::from_values(values(pairs), Vec::new())
+            .unwrap_err()
+            .0
+    }
+
+    #[test]
+    fn defaults_fill_in_the_optional_keys() {
+        let config = Config::from_values(values(&[]), Vec::new()).unwrap();
+        assert_eq!(config.db_max_connections, 5);
+        assert!(!config.run_migrations);
+        assert_eq!(config.token_lifetime_hours, 8);
+        assert_eq!(config.public_base_url, "http://127.0.0.1:3000");
+        assert_eq!(config.cookie_same_site, SameSite::Lax);
+        assert_eq!(config.initial_admin_email, None);
+        assert_eq!(config.shutdown_timeout_secs, 10);
+    }
+
+    #[test]
+    fn values_are_trimmed_and_the_base_url_loses_its_trailing_slash() {
+        let config = Config::from_values(
+            values(&[
+                ("PUBLIC_BASE_URL", " https://astro.example/ "),
+                ("COOKIE_SAME_SITE", "Strict"),
+                ("INITIAL_ADMIN_EMAIL", "  "),
+            ]),
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(config.public_base_url, "https://astro.example");
+        assert_eq!(config.cookie_same_site, SameSite::Strict);
+        assert_eq!(config.initial_admin_email, None);
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let mut values = values(&[("API_PORT", "eighty"), ("SALT", "short")]);
+        values.remove("NASA_API_KEY");
+        let problems = Config::from_values(values, vec!["From the caller".to_string()])
+            .unwrap_err()
+            .0;
+        assert_eq!(
+            problems,
+            [
+                "From the caller",
+                "API_PORT has an invalid value: \"eighty\"",
+                "NASA_API_KEY is missing",
+                "SALT has to be at least 8 characters long",
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_optional_values_are_problems_too() {
+        assert_eq!(
+            problems(&[("DB_MAX_CONNECTIONS", "0")]),
+            ["DB_MAX_CONNECTIONS has to be at least 1"]
+        );
+        assert_eq!(
+            problems(&[("PUBLIC_BASE_URL", "astro.example")]),
+            ["PUBLIC_BASE_URL has to start with http:// or https://"]
+        );
+        assert_eq!(
+            problems(&[("COOKIE_SAME_SITE", "sometimes")]),
+            ["COOKIE_SAME_SITE has to be strict, lax or none, not \"sometimes\""]
+        );
+    }
+}
@@ -1,36 +1,162 @@
+use std::collections::HashMap;
+
 use axum::Json;
-use serde_json::Value;
-use std::sync::{Arc, Mutex};
+use serde_json::{json, Value};
 
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{PgPool, Row};
+use chrono::{DateTime, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgPoolOptions, PgRow};
+use sqlx::{PgPool, Postgres, Row, Transaction};
+use tokio::sync::broadcast;
+use tracing::trace;
 
-use crate::error::AppError;
+use crate::config::Config;
+use crate::error::{AppError, FieldError};
+use crate::models::activity::{ActivityId, ActivityKind, FeedItem};
+use crate::models::audit::{AuditAction, AuditEntry, AuditEntryId, AuditQuery};
+use crate::models::ban::{Ban, BanId};
+use crate::models::event::LiveEvent;
+use crate::models::export::{AccountExport, ExportedLike};
+use crate::models::follow::{Follow, FollowId};
 use crate::models::nasaquery::NasaQuery;
+use crate::models::notification::{Notification, NotificationId, NotificationKind};
 use crate::models::post::{CreatePost, Post, PostId, UpdatePost};
-use crate::models::user::{User, UserSignup};
+use crate::models::report::{Report, ReportId, ReportResolution, ReportTarget};
+use crate::models::revision::{PostRevision, PostRevisionId, RevisionSource};
+use crate::models::role::Role;
+use crate::models::similarity::SimilarPost;
+use crate::models::stats::{ActiveUser, DailyCount, NasaFetchCounts, SiteStats};
+use crate::models::syndication::SyndicationEntry;
+use crate::models::user::{
+    BulkAction, BulkUserAction, User, UserListEntry, UserListQuery, UserSignup,
+};
 use crate::models::vote::{CreateVote, Vote, VoteId};
+
+/// How many entries of the "Following" feed we show on a single page
+pub const FEED_PAGE_SIZE: i64 = 20;
+
+/// How many posts we show in the "also liked" and "recommended for you" sections
+pub const RECOMMENDATION_LIMIT: i64 = 5;
+
+/// How many audit log entries we show on a single page
+pub const AUDIT_PAGE_SIZE: i64 = 50;
+
+/// How many users we show on a single page of the user management table
+pub const USER_PAGE_SIZE: i64 = 50;
+
+/// Users with everything the user management table shows about them
+const USER_LIST_SELECT: &str = r#"
+    SELECT users.id, users.email, users.role, users.created_on, users.last_login_on,
+        EXISTS (
+            SELECT * FROM bans
+            WHERE bans.user_id = users.id
+            AND bans.lifted_on IS NULL
+            AND (bans.expires_on IS NULL OR bans.expires_on > NOW())
+        ) AS is_banned,
+        (SELECT COUNT(*) FROM votes WHERE votes.user_id = users.id) AS vote_count
+    FROM users
+"#;
+
+/// How many days of signups and votes the admin dashboard charts
+pub const STATS_DAYS: i32 = 30;
+
+/// How many users the admin dashboard lists as most active
+const MOST_ACTIVE_LIMIT: i64 = 10;
+
+/// Reports joined with everything the moderation queue shows about them
+const REPORT_SELECT: &str = r#"
+    SELECT reports.*, reporter.email AS reporter_email, resolver.email AS resolved_by_email,
+        CASE reports.target_kind
+            WHEN 'post' THEN (SELECT title FROM posts WHERE posts.id = reports.target_id)
+            WHEN 'user' THEN (SELECT email FROM users WHERE users.id = reports.target_id)
+        END AS target_label
+    FROM reports
+    LEFT JOIN users reporter ON reporter.id = reports.reporter_id
+    LEFT JOIN users resolver ON resolver.id = reports.resolved_by
+"#;
+
+/// Advisory lock held by every transaction that changes roles or bans, see `lock_roles`
+const ROLE_LOCK_KEY: i64 = 0x617374726f; // "astro"
+
+/// How many live events a slow subscriber can fall behind before it starts missing some
+const LIVE_EVENT_CAPACITY: usize = 100;
+
 #[derive(Clone)]
 pub struct Store {
     pub conn_pool: PgPool,
-    pub posts: Arc<Mutex<Vec<Post>>>,
+    pub events: broadcast::Sender<LiveEvent>,
 }
 
-pub async fn new_pool() -> PgPool {
-    let db_url = std::env::var("DATABASE_URL").unwrap();
+pub async fn new_pool(config: &Config) -> PgPool {
     PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
+        .max_connections(config.db_max_connections)
+        .connect(&config.database_url)
         .await
         .unwrap()
 }
 
+/// Only connects the first time a query needs it, for an app whose routes run on an in-memory
+/// `Repository` and may never touch Postgres
+pub fn new_lazy_pool(config: &Config) -> Result<PgPool, AppError> {
+    let pool = PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .connect_lazy(&config.database_url)?;
+    Ok(pool)
+}
+
+/// Every migration in `./migrations`, built into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Brings the schema up to date, for when `RUN_MIGRATIONS` is on or the admin CLI is asked to
+pub async fn run_migrations(pool: &PgPool) -> Result<(), AppError> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
 impl Store {
     pub fn with_pool(pool: PgPool) -> Self {
+        let (events, _) = broadcast::channel(LIVE_EVENT_CAPACITY);
         Self {
             conn_pool: pool,
-            posts: Default::default(),
+            events,
+        }
+    }
+
+    /// Sends an event to every open page. Nobody listening isn't an error, so the result is ignored.
+    pub fn publish(&self, event: LiveEvent) {
+        let _ = self.events.send(event);
+    }
+
+    pub async fn publish_vote_count(&self, post_id: i32) -> Result<(), AppError> {
+        let num_likes = self.get_number_of_votes_for_post(post_id).await?;
+        self.publish(LiveEvent::VoteCount {
+            post_id: PostId(post_id),
+            num_likes,
+        });
+        Ok(())
+    }
+
+    // Health ----------------------------------------------------------------------------------------------------------
+    /// The cheapest query there is, fails if the pool can't hand out a working connection
+    pub async fn ping(&self) -> Result<(), AppError> {
+        sqlx::query("SELECT 1").execute(&self.conn_pool).await?;
+        Ok(())
+    }
+
+    /// The newest migration that was applied successfully, `None` if migrations have never run
+    pub async fn get_migration_version(&self) -> Result<Option<i64>, AppError> {
+        let res = sqlx::query(r#"SELECT to_regclass('_sqlx_migrations') IS NOT NULL AS has_table"#)
+            .fetch_one(&self.conn_pool)
+            .await?;
+        if !res.get::<bool, _>("has_table") {
+            return Ok(None);
         }
+
+        let res =
+            sqlx::query(r#"SELECT MAX(version) AS version FROM _sqlx_migrations WHERE success"#)
+                .fetch_one(&self.conn_pool)
+                .await?;
+        Ok(res.get("version"))
     }
 
     // Users -----------------------------------------------------------------------------------------------------------
@@ -58,14 +184,198 @@ impl Store {
         Ok(id)
     }
 
+    pub async fn get_user_email_by_id(&self, user_id: i32) -> Result<String, AppError> {
+        let res = sqlx::query(r#"SELECT email FROM users WHERE id=$1"#)
+            .bind(user_id)
+            .fetch_one(&self.conn_pool)
+            .await?;
+
+        let email: String = res.get("email");
+
+        Ok(email)
+    }
+
+    pub async fn record_login(&self, email: String) -> Result<(), AppError> {
+        sqlx::query(r#"UPDATE users SET last_login_on = NOW() WHERE email = $1"#)
+            .bind(email)
+            .execute(&self.conn_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// A page of the admin user management table, optionally only users whose email contains
+    /// `query.search`
+    pub async fn get_users(
+        &self,
+        query: &UserListQuery,
+        page_size: i64,
+    ) -> Result<Vec<UserListEntry>, AppError> {
+        let page = query.page.unwrap_or(0).max(0);
+        let res = sqlx::query(&format!(
+            r#"
+            {USER_LIST_SELECT}
+            WHERE $1::text IS NULL OR users.email ILIKE '%' || $1 || '%'
+            ORDER BY users.email
+            LIMIT $2 OFFSET $3
+            "#
+        ))
+        .bind(query.search())
+        .bind(page_size)
+        .bind(page * page_size)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        res.iter().map(user_list_entry_from_row).collect()
+    }
+
+    /// Everyone with admin rights, owners first
+    pub async fn get_admins(&self) -> Result<Vec<UserListEntry>, AppError> {
+        let res = sqlx::query(&format!(
+            r#"
+            {USER_LIST_SELECT}
+            WHERE users.role IN ('admin', 'owner')
+            ORDER BY users.role = 'owner' DESC, users.email
+            "#
+        ))
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        res.iter().map(user_list_entry_from_row).collect()
+    }
+
+    pub async fn get_user_summary_by_email(
+        &self,
+        email: String,
+    ) -> Result<UserListEntry, AppError> {
+        let res = sqlx::query(&format!("{USER_LIST_SELECT} WHERE users.email = $1"))
+            .bind(email)
+            .fetch_optional(&self.conn_pool)
+            .await?
+            .ok_or(AppError::UserNotFound)?;
+
+        user_list_entry_from_row(&res)
+    }
+
+    /// Everything we have on one user, see the settings page
+    pub async fn export_account(&self, email: String) -> Result<AccountExport, AppError> {
+        let profile = self.get_user_summary_by_email(email.clone()).await?;
+
+        let likes = sqlx::query(
+            r#"
+            SELECT posts.id, posts.title, posts.apod_date, votes.created_on
+            FROM votes
+            INNER JOIN posts ON posts.id = votes.post_id
+            WHERE votes.user_id = $1
+            ORDER BY votes.created_on
+            "#,
+        )
+        .bind(profile.id)
+        .fetch_all(&self.conn_pool)
+        .await?
+        .iter()
+        .map(|row| ExportedLike {
+            post_id: PostId(row.get("id")),
+            title: row.get("title"),
+            apod_date: row.get("apod_date"),
+            liked_on: row.get("created_on"),
+        })
+        .collect();
+
+        let followers = sqlx::query(
+            r#"
+            SELECT users.email FROM follows
+            INNER JOIN users
+            ON users.id = follows.follower_id
+            WHERE follows.followed_id = $1
+            ORDER BY users.email
+            "#,
+        )
+        .bind(profile.id)
+        .fetch_all(&self.conn_pool)
+        .await?
+        .iter()
+        .map(|row| row.get("email"))
+        .collect();
+
+        let reports = sqlx::query(&format!(
+            "{} WHERE reports.reporter_id = $1 ORDER BY reports.created_on",
+            REPORT_SELECT
+        ))
+        .bind(profile.id)
+        .fetch_all(&self.conn_pool)
+        .await?
+        .iter()
+        .map(report_from_row)
+        .collect();
+
+        Ok(AccountExport {
+            exported_on: Utc::now(),
+            likes,
+            following: self.get_followed_emails(profile.id).await?,
+            followers,
+            notifications: self.get_notifications_for_user(profile.id).await?,
+            bans: self.get_ban_history_by_email(email).await?,
+            reports,
+            profile,
+        })
+    }
+
+    /// Deletes a user and, through `ON DELETE CASCADE`, everything of theirs, for users deleting their
+    /// own account. The last admin can't delete themselves, same as they can't be demoted.
+    pub async fn delete_user_by_email(&self, email: String) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let (user_id, role) = lock_user_role(&mut tx, &email).await?;
+        if role.is_admin() {
+            ensure_not_last_admin(&mut tx, user_id).await?;
+        }
+
+        let liked_posts: Vec<i32> = sqlx::query(
+            r#"
+            DELETE FROM votes WHERE user_id = $1 RETURNING post_id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.get("post_id"))
+        .collect();
+
+        sqlx::query(
+            r#"
+            DELETE FROM users WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        record_audit(
+            &mut tx,
+            &email,
+            AuditAction::DeleteUser,
+            &email,
+            json!({ "self": true }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        // Their likes are gone, so everyone else's pages need the new counts
+        for post_id in liked_posts {
+            self.publish_vote_count(post_id).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn create_user(&self, user: UserSignup) -> Result<Json<Value>, AppError> {
-        let result =
-            sqlx::query("INSERT INTO users(email, password, is_banned) values ($1, $2, false)")
-                .bind(&user.email)
-                .bind(&user.password)
-                .execute(&self.conn_pool)
-                .await
-                .map_err(|_| AppError::InternalServerError)?;
+        let result = sqlx::query("INSERT INTO users(email, password) values ($1, $2)")
+            .bind(&user.email)
+            .bind(&user.password)
+            .execute(&self.conn_pool)
+            .await?;
 
         if result.rows_affected() < 1 {
             Err(AppError::InternalServerError)
@@ -76,36 +386,38 @@ impl Store {
         }
     }
 
-    pub async fn determine_if_user_banned(&mut self, email: String) -> Result<bool, AppError> {
-        let res = sqlx::query(r#"SELECT * FROM users WHERE email=$1"#)
-            .bind(email)
-            .fetch_one(&self.conn_pool)
-            .await?;
-        Ok(res.get("is_banned"))
-    }
-
-    pub async fn determine_if_user_admin(&mut self, email: String) -> Result<bool, AppError> {
+    /// A user is banned while they have a ban that hasn't been lifted or run out
+    pub async fn determine_if_user_banned(&self, email: String) -> Result<bool, AppError> {
         let res = sqlx::query(
             r#"
-            SELECT * FROM users 
-            INNER JOIN admins 
-            ON admins.admin_user_id = users.id
-            WHERE email=$1;
-        "#,
+            SELECT EXISTS (
+                SELECT * FROM bans
+                INNER JOIN users ON users.id = bans.user_id
+                WHERE users.email = $1
+                AND bans.lifted_on IS NULL
+                AND (bans.expires_on IS NULL OR bans.expires_on > NOW())
+            ) AS is_banned
+            "#,
         )
         .bind(email)
-        .fetch_optional(&self.conn_pool)
+        .fetch_one(&self.conn_pool)
         .await?;
+        Ok(res.get("is_banned"))
+    }
 
-        if res.is_none() {
-            Ok(false)
-        } else {
-            Ok(true)
-        }
+    pub async fn get_user_role_by_email(&self, email: String) -> Result<Role, AppError> {
+        let res = sqlx::query(r#"SELECT role FROM users WHERE email=$1"#)
+            .bind(email)
+            .fetch_optional(&self.conn_pool)
+            .await?
+            .ok_or(AppError::UserNotFound)?;
+
+        let role: String = res.get("role");
+        role.parse().map_err(|_| AppError::InternalServerError)
     }
 
     // Posts -----------------------------------------------------------------------------------------------------------
-    pub async fn get_all_posts(&mut self) -> Result<Vec<Post>, AppError> {
+    pub async fn get_all_posts(&self) -> Result<Vec<Post>, AppError> {
         let res = sqlx::query(
             r#"
             SELECT * FROM posts;
@@ -129,7 +441,7 @@ impl Store {
         Ok(posts)
     }
 
-    pub async fn get_post_by_id(&mut self, post_id: i32) -> Result<Post, AppError> {
+    pub async fn get_post_by_id(&self, post_id: i32) -> Result<Post, AppError> {
         let res = sqlx::query(
             r#"
             SELECT * FROM posts WHERE id=$1
@@ -151,7 +463,7 @@ impl Store {
         Ok(post)
     }
 
-    pub async fn get_top_posts(&mut self) -> Result<Vec<i32>, AppError> {
+    pub async fn get_top_posts(&self) -> Result<Vec<i32>, AppError> {
         // This query courtesy of https://www.tutorialspoint.com/count-number-of-times-value-appears-in-particular-column-in-mysql
         let res = sqlx::query(
             r#"
@@ -166,7 +478,7 @@ impl Store {
         Ok(posts)
     }
 
-    pub async fn add_post(&mut self, new_post: CreatePost) -> Result<Post, AppError> {
+    pub async fn add_post(&self, new_post: CreatePost) -> Result<Post, AppError> {
         let res = sqlx::query(
             r#"
             INSERT INTO posts (title, query_string, explanation, img_url, apod_date) 
@@ -191,62 +503,25 @@ impl Store {
             apod_date: res.get("apod_date"),
         };
 
+        self.publish(LiveEvent::NewPost(post.clone()));
+
         Ok(post)
     }
 
-    pub async fn delete_post_by_id(&mut self, post_id: i32) -> Result<(), AppError> {
-        sqlx::query(
-            r#"
-    DELETE FROM posts WHERE id = $1
-    "#,
-        )
-        .bind(post_id)
-        .execute(&self.conn_pool)
-        .await
-        .unwrap();
+    /// Deletes a post along with its audit entry, which keeps a copy of the post
+    pub async fn delete_post_by_id(
+        &self,
+        actor_email: String,
+        post_id: i32,
+    ) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+        delete_post(&mut tx, &actor_email, post_id, None).await?;
+        tx.commit().await?;
 
         Ok(())
     }
 
-    pub async fn update_post_by_id(&mut self, new_post: UpdatePost) -> Result<Post, AppError> {
-        sqlx::query(
-            r#"
-            UPDATE posts
-            SET title = $1, query_string = $2, explanation = $3, img_url = $4, apod_date = $5
-            WHERE id = $6
-            "#,
-        )
-        .bind(new_post.title)
-        .bind(new_post.query_string)
-        .bind(new_post.explanation)
-        .bind(new_post.img_url)
-        .bind(new_post.apod_date)
-        .bind(new_post.id.0)
-        .execute(&self.conn_pool)
-        .await?;
-
-        let res = sqlx::query(
-            r#"
-            SELECT * FROM posts WHERE id=$1
-            "#,
-        )
-        .bind(new_post.id.0)
-        .fetch_one(&self.conn_pool)
-        .await?;
-
-        let new_post = Post {
-            id: PostId(res.get("id")),
-            title: res.get("title"),
-            query_string: res.get("query_string"),
-            explanation: res.get("explanation"),
-            img_url: res.get("img_url"),
-            apod_date: res.get("apod_date"),
-        };
-
-        Ok(new_post)
-    }
-
-    pub async fn get_post_by_query_string(&mut self, query: NasaQuery) -> Result<Post, AppError> {
+    pub async fn get_post_by_query_string(&self, query: NasaQuery) -> Result<Post, AppError> {
         let res = sqlx::query(
             r#"
             SELECT * FROM posts WHERE query_string=$1
@@ -268,10 +543,7 @@ impl Store {
         Ok(post)
     }
 
-    pub async fn check_cache_by_query_string(
-        &mut self,
-        query: NasaQuery,
-    ) -> Result<bool, AppError> {
+    pub async fn check_cache_by_query_string(&self, query: NasaQuery) -> Result<bool, AppError> {
         let res = sqlx::query!(
             r#"
             SELECT EXISTS ( SELECT * FROM posts WHERE query_string=$1);
@@ -281,7 +553,7 @@ impl Store {
         .fetch_one(&self.conn_pool)
         .await?;
 
-        println!("{:?}", res);
+        trace!(query_string = %query.query_string, exists = ?res.exists, "Checked APOD cache");
         if res.exists == Some(false) {
             Ok(false)
         } else {
@@ -289,7 +561,7 @@ impl Store {
         }
     }
 
-    pub async fn get_user_posts_by_id(&mut self, user_id: i32) -> Result<Vec<Post>, AppError> {
+    pub async fn get_user_posts_by_id(&self, user_id: i32) -> Result<Vec<Post>, AppError> {
         let res = sqlx::query(
             r#"
             SELECT * FROM posts
@@ -318,7 +590,7 @@ impl Store {
     }
 
     pub async fn determine_if_user_liked_post(
-        &mut self,
+        &self,
         user_id: i32,
         post_id: i32,
     ) -> Result<bool, AppError> {
@@ -339,8 +611,157 @@ impl Store {
         }
     }
 
+    // Revisions -------------------------------------------------------------------------------------------------------
+    /// Updates a post, saves the new version as a revision and audits the change as `actor_email`,
+    /// all in one transaction. The first time a post changes, the version it was ingested with is
+    /// saved too so it can be rolled back to.
+    pub async fn update_post_with_revision(
+        &self,
+        actor_email: String,
+        old_post: Post,
+        new_post: UpdatePost,
+        source: RevisionSource,
+    ) -> Result<Post, AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            SELECT id FROM posts WHERE id = $1 FOR UPDATE
+            "#,
+        )
+        .bind(new_post.id.0)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::PostNotFound)?;
+
+        let title_taken = sqlx::query(
+            r#"
+            SELECT id FROM posts WHERE title = $1 AND id <> $2
+            "#,
+        )
+        .bind(&new_post.title)
+        .bind(new_post.id.0)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if title_taken.is_some() {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "title",
+                "Another post already has that title",
+            )]));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO post_revisions (post_id, source, title, explanation, img_url, apod_date, created_on)
+            SELECT id, $2, title, explanation, img_url, apod_date, created_on FROM posts
+            WHERE id = $1
+            AND NOT EXISTS (SELECT * FROM post_revisions WHERE post_id = $1)
+            "#,
+        )
+        .bind(new_post.id.0)
+        .bind(RevisionSource::Original.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE posts
+            SET title = $1, query_string = $2, explanation = $3, img_url = $4, apod_date = $5
+            WHERE id = $6
+            "#,
+        )
+        .bind(&new_post.title)
+        .bind(&new_post.query_string)
+        .bind(&new_post.explanation)
+        .bind(&new_post.img_url)
+        .bind(&new_post.apod_date)
+        .bind(new_post.id.0)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO post_revisions (post_id, editor_id, source, title, explanation, img_url, apod_date)
+            VALUES ($1, (SELECT id FROM users WHERE email = $2), $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(new_post.id.0)
+        .bind(&actor_email)
+        .bind(source.to_string())
+        .bind(&new_post.title)
+        .bind(&new_post.explanation)
+        .bind(&new_post.img_url)
+        .bind(&new_post.apod_date)
+        .execute(&mut *tx)
+        .await?;
+
+        let updated_post = Post {
+            id: new_post.id,
+            title: new_post.title,
+            query_string: new_post.query_string,
+            explanation: new_post.explanation,
+            img_url: new_post.img_url,
+            apod_date: new_post.apod_date,
+        };
+        record_audit(
+            &mut tx,
+            &actor_email,
+            AuditAction::UpdatePost,
+            &format!("post {}", updated_post.id),
+            json!({ "before": old_post, "after": updated_post, "source": source }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(updated_post)
+    }
+
+    /// Newest first
+    pub async fn get_post_revisions(&self, post_id: i32) -> Result<Vec<PostRevision>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT post_revisions.*, users.email AS editor_email
+            FROM post_revisions
+            LEFT JOIN users ON users.id = post_revisions.editor_id
+            WHERE post_revisions.post_id = $1
+            ORDER BY post_revisions.id DESC
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(res.iter().map(revision_from_row).collect())
+    }
+
+    pub async fn get_post_revision(
+        &self,
+        post_id: i32,
+        revision_id: PostRevisionId,
+    ) -> Result<PostRevision, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT post_revisions.*, users.email AS editor_email
+            FROM post_revisions
+            LEFT JOIN users ON users.id = post_revisions.editor_id
+            WHERE post_revisions.post_id = $1 AND post_revisions.id = $2
+            "#,
+        )
+        .bind(post_id)
+        .bind(revision_id.0)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::RevisionNotFound)?;
+
+        Ok(revision_from_row(&res))
+    }
+
     // Votes -----------------------------------------------------------------------------------------------------------
-    pub async fn create_vote(&mut self, new_vote: CreateVote) -> Result<Vote, AppError> {
+    /// The vote and its entry in followers' feeds are written together, or not at all
+    pub async fn create_vote(&self, new_vote: CreateVote) -> Result<Vote, AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
         let res = sqlx::query(
             r#"
             INSERT INTO votes (post_id, user_id) VALUES($1, $2)
@@ -349,7 +770,7 @@ impl Store {
         )
         .bind(new_vote.post_id.0)
         .bind(new_vote.user_id)
-        .fetch_one(&self.conn_pool)
+        .fetch_one(&mut *tx)
         .await?;
 
         let created_vote = Vote {
@@ -358,10 +779,24 @@ impl Store {
             user_id: res.get("user_id"),
         };
 
+        record_activity(
+            &mut tx,
+            created_vote.user_id,
+            created_vote.post_id.0,
+            ActivityKind::Liked,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        self.publish_vote_count(created_vote.post_id.0).await?;
+
         Ok(created_vote)
     }
 
-    pub async fn delete_vote(&mut self, old_vote: CreateVote) -> Result<(), AppError> {
+    pub async fn delete_vote(&self, old_vote: CreateVote) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
         sqlx::query(
             r#"
             DELETE FROM votes WHERE user_id=$1 AND post_id=$2
@@ -369,13 +804,26 @@ impl Store {
         )
         .bind(old_vote.user_id)
         .bind(old_vote.post_id.0)
-        .execute(&self.conn_pool)
+        .execute(&mut *tx)
+        .await?;
+
+        // Take the like back out of everyone's feed too
+        delete_activity(
+            &mut tx,
+            old_vote.user_id,
+            old_vote.post_id.0,
+            ActivityKind::Liked,
+        )
         .await?;
 
+        tx.commit().await?;
+
+        self.publish_vote_count(old_vote.post_id.0).await?;
+
         Ok(())
     }
 
-    pub async fn get_number_of_votes_for_post(&mut self, post_id: i32) -> Result<i64, AppError> {
+    pub async fn get_number_of_votes_for_post(&self, post_id: i32) -> Result<i64, AppError> {
         let res = sqlx::query!(
             r#"
             SELECT COUNT(posts.id) AS "num_votes" FROM
@@ -395,85 +843,423 @@ WHERE post_id = $1;
         Ok(num_votes)
     }
 
-    // Admin -----------------------------------------------------------------------------------------------------------
-    pub async fn ban_user_by_email(&mut self, email_to_ban: String) -> Result<(), AppError> {
+    // Recommendations -------------------------------------------------------------------------------------------------
+    /// Rebuilds `post_similarities` from scratch out of the `votes` table. Two posts are similar when
+    /// the same people liked them, scored as the cosine similarity of their sets of voters.
+    pub async fn refresh_post_similarities(&self) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
         sqlx::query(
             r#"
-            UPDATE users
-            SET is_banned=true
-            WHERE email = $1
+            DELETE FROM post_similarities
             "#,
         )
-        .bind(email_to_ban)
-        .execute(&self.conn_pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(())
-    }
-
-    pub async fn unban_user_by_email(&mut self, email_to_ban: String) -> Result<(), AppError> {
         sqlx::query(
             r#"
-            UPDATE users
-            SET is_banned=false
-            WHERE email = $1
+            WITH post_likes AS (
+                SELECT post_id, COUNT(*) AS likes FROM votes GROUP BY post_id
+            )
+            INSERT INTO post_similarities (post_id, similar_post_id, score)
+            SELECT a.post_id, b.post_id,
+                COUNT(*)::float8 / SQRT((a_likes.likes * b_likes.likes)::float8)
+            FROM votes a
+            INNER JOIN votes b ON a.user_id = b.user_id AND a.post_id <> b.post_id
+            INNER JOIN post_likes a_likes ON a_likes.post_id = a.post_id
+            INNER JOIN post_likes b_likes ON b_likes.post_id = b.post_id
+            GROUP BY a.post_id, b.post_id, a_likes.likes, b_likes.likes
             "#,
         )
-        .bind(email_to_ban)
-        .execute(&self.conn_pool)
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(())
     }
 
-    pub async fn promote_admin_by_email(
-        &mut self,
-        email_to_promote: String,
-    ) -> Result<(), AppError> {
-        // Get UserId
+    pub async fn get_similar_posts(
+        &self,
+        post_id: i32,
+        limit: i64,
+    ) -> Result<Vec<SimilarPost>, AppError> {
         let res = sqlx::query(
             r#"
-            SELECT id FROM users WHERE email=$1
+            SELECT posts.id, posts.title, posts.img_url, post_similarities.score
+            FROM post_similarities
+            INNER JOIN posts ON posts.id = post_similarities.similar_post_id
+            WHERE post_similarities.post_id = $1
+            ORDER BY post_similarities.score DESC, posts.id
+            LIMIT $2
             "#,
         )
-        .bind(email_to_promote)
-        .fetch_one(&self.conn_pool)
-        .await?;
+        .bind(post_id)
+        .bind(limit)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let similar_posts: Vec<_> = res
+            .into_iter()
+            .map(|row| SimilarPost {
+                id: PostId(row.get("id")),
+                title: row.get("title"),
+                img_url: row.get("img_url"),
+                score: row.get("score"),
+            })
+            .collect();
+
+        Ok(similar_posts)
+    }
+
+    /// `get_similar_posts` for every post in `post_ids` in one query, keyed by post id. Posts nobody
+    /// also liked anything with are left out of the map.
+    pub async fn get_similar_posts_for_posts(
+        &self,
+        post_ids: &[i32],
+        limit: i64,
+    ) -> Result<HashMap<i32, Vec<SimilarPost>>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT post_id, id, title, img_url, score
+            FROM (
+                SELECT post_similarities.post_id, posts.id, posts.title, posts.img_url,
+                    post_similarities.score,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY post_similarities.post_id
+                        ORDER BY post_similarities.score DESC, posts.id
+                    ) AS rank
+                FROM post_similarities
+                INNER JOIN posts ON posts.id = post_similarities.similar_post_id
+                WHERE post_similarities.post_id = ANY($1)
+            ) ranked
+            WHERE rank <= $2
+            ORDER BY post_id, rank
+            "#,
+        )
+        .bind(post_ids)
+        .bind(limit)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let mut similar_posts: HashMap<i32, Vec<SimilarPost>> = HashMap::new();
+        for row in res {
+            similar_posts
+                .entry(row.get("post_id"))
+                .or_default()
+                .push(SimilarPost {
+                    id: PostId(row.get("id")),
+                    title: row.get("title"),
+                    img_url: row.get("img_url"),
+                    score: row.get("score"),
+                });
+        }
+
+        Ok(similar_posts)
+    }
+
+    /// Posts similar to what `user_id` already liked, that they haven't liked yet
+    pub async fn get_recommended_posts_for_user(
+        &self,
+        user_id: i32,
+        limit: i64,
+    ) -> Result<Vec<Post>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT posts.*, SUM(post_similarities.score) AS total_score
+            FROM votes
+            INNER JOIN post_similarities ON post_similarities.post_id = votes.post_id
+            INNER JOIN posts ON posts.id = post_similarities.similar_post_id
+            WHERE votes.user_id = $1
+            AND NOT EXISTS (
+                SELECT 1 FROM votes liked WHERE liked.user_id = $1 AND liked.post_id = posts.id
+            )
+            GROUP BY posts.id
+            ORDER BY total_score DESC, posts.id
+            LIMIT $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let posts: Vec<_> = res
+            .into_iter()
+            .map(|row| Post {
+                id: PostId(row.get("id")),
+                title: row.get("title"),
+                query_string: row.get("query_string"),
+                explanation: row.get("explanation"),
+                img_url: row.get("img_url"),
+                apod_date: row.get("apod_date"),
+            })
+            .collect();
+
+        Ok(posts)
+    }
+
+    // Syndication -----------------------------------------------------------------------------------------------------
+    pub async fn get_newest_entries(&self, limit: i64) -> Result<Vec<SyndicationEntry>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT * FROM posts ORDER BY created_on DESC, id DESC LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let entries: Vec<_> = res
+            .into_iter()
+            .map(|row| SyndicationEntry {
+                added_on: row.get("created_on"),
+                post: Post {
+                    id: PostId(row.get("id")),
+                    title: row.get("title"),
+                    query_string: row.get("query_string"),
+                    explanation: row.get("explanation"),
+                    img_url: row.get("img_url"),
+                    apod_date: row.get("apod_date"),
+                },
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    pub async fn get_user_liked_entries(
+        &self,
+        user_id: i32,
+        limit: i64,
+    ) -> Result<Vec<SyndicationEntry>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT posts.*, votes.created_on AS liked_on FROM posts
+            INNER JOIN votes
+            ON posts.id = votes.post_id
+            WHERE votes.user_id = $1
+            ORDER BY votes.created_on DESC, votes.id DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let entries: Vec<_> = res
+            .into_iter()
+            .map(|row| SyndicationEntry {
+                added_on: row.get("liked_on"),
+                post: Post {
+                    id: PostId(row.get("id")),
+                    title: row.get("title"),
+                    query_string: row.get("query_string"),
+                    explanation: row.get("explanation"),
+                    img_url: row.get("img_url"),
+                    apod_date: row.get("apod_date"),
+                },
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    // Follows ---------------------------------------------------------------------------------------------------------
+    /// Returns `None` if `follower_id` was already following that user. Nobody can follow themselves.
+    pub async fn follow_user_by_email(
+        &self,
+        follower_id: i32,
+        email_to_follow: String,
+    ) -> Result<Option<Follow>, AppError> {
+        let followed_id = self.get_user_id_by_email(email_to_follow).await?;
+        if followed_id == follower_id {
+            return Err(AppError::CannotActOnYourself);
+        }
 
-        let user_id: i32 = res.get("id");
+        let res = sqlx::query(
+            r#"
+            INSERT INTO follows (follower_id, followed_id) VALUES ($1, $2)
+            ON CONFLICT (follower_id, followed_id) DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(follower_id)
+        .bind(followed_id)
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        let follow = res.map(|row| Follow {
+            id: FollowId(row.get("id")),
+            follower_id: row.get("follower_id"),
+            followed_id: row.get("followed_id"),
+        });
+
+        Ok(follow)
+    }
 
-        // Add to Admins
+    pub async fn unfollow_user_by_email(
+        &self,
+        follower_id: i32,
+        email_to_unfollow: String,
+    ) -> Result<(), AppError> {
         sqlx::query(
             r#"
-            INSERT INTO admins (admin_user_id) VALUES ($1)
+            DELETE FROM follows
+            WHERE follower_id = $1
+            AND followed_id = (SELECT id FROM users WHERE email = $2)
+            "#,
+        )
+        .bind(follower_id)
+        .bind(email_to_unfollow)
+        .execute(&self.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_followed_emails(&self, follower_id: i32) -> Result<Vec<String>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT users.email FROM follows
+            INNER JOIN users
+            ON users.id = follows.followed_id
+            WHERE follows.follower_id = $1
+            ORDER BY users.email
+            "#,
+        )
+        .bind(follower_id)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let emails: Vec<_> = res.into_iter().map(|row| row.get("email")).collect();
+
+        Ok(emails)
+    }
+
+    // Activity --------------------------------------------------------------------------------------------------------
+    /// Newest-first activity of everyone `user_id` follows. `page` starts at 0.
+    pub async fn feed_for_user(
+        &self,
+        user_id: i32,
+        page: i64,
+        page_size: i64,
+    ) -> Result<Vec<FeedItem>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT activity.id, activity.kind, activity.created_on, users.email,
+                posts.id AS post_id, posts.title, posts.img_url, posts.apod_date
+            FROM activity
+            INNER JOIN follows ON follows.followed_id = activity.user_id
+            INNER JOIN users ON users.id = activity.user_id
+            INNER JOIN posts ON posts.id = activity.post_id
+            WHERE follows.follower_id = $1
+            ORDER BY activity.created_on DESC, activity.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(page_size)
+        .bind(page.max(0) * page_size)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let feed: Vec<_> = res
+            .into_iter()
+            .map(|row| FeedItem {
+                id: ActivityId(row.get("id")),
+                actor_email: row.get("email"),
+                kind: row.get("kind"),
+                post_id: PostId(row.get("post_id")),
+                title: row.get("title"),
+                img_url: row.get("img_url"),
+                apod_date: row.get("apod_date"),
+                created_on: row.get("created_on"),
+            })
+            .collect();
+
+        Ok(feed)
+    }
+
+    // Notifications ---------------------------------------------------------------------------------------------------
+    pub async fn create_notification(
+        &self,
+        user_id: i32,
+        kind: NotificationKind,
+        message: String,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO notifications (user_id, kind, message) VALUES ($1, $2, $3)
             "#,
         )
         .bind(user_id)
+        .bind(kind.to_string())
+        .bind(message)
         .execute(&self.conn_pool)
         .await?;
 
         Ok(())
     }
 
-    pub async fn demote_admin_by_email(&mut self, email_to_demote: String) -> Result<(), AppError> {
-        // Get UserId
+    pub async fn get_notifications_for_user(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<Notification>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT * FROM notifications
+            WHERE user_id = $1
+            ORDER BY created_on DESC, id DESC
+            LIMIT 100
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let notifications: Vec<_> = res
+            .into_iter()
+            .map(|row| Notification {
+                id: NotificationId(row.get("id")),
+                user_id: row.get("user_id"),
+                kind: row.get("kind"),
+                message: row.get("message"),
+                is_read: row.get("is_read"),
+                created_on: row.get("created_on"),
+            })
+            .collect();
+
+        Ok(notifications)
+    }
+
+    pub async fn count_unread_notifications(&self, user_id: i32) -> Result<i64, AppError> {
         let res = sqlx::query(
             r#"
-            SELECT id FROM users WHERE email=$1
+            SELECT COUNT(*) AS unread FROM notifications WHERE user_id = $1 AND is_read = false
             "#,
         )
-        .bind(email_to_demote)
+        .bind(user_id)
         .fetch_one(&self.conn_pool)
         .await?;
 
-        let user_id: i32 = res.get("id");
+        Ok(res.get("unread"))
+    }
 
-        // Remove from Admins
+    pub async fn mark_notification_read(
+        &self,
+        user_id: i32,
+        notification_id: NotificationId,
+    ) -> Result<(), AppError> {
+        // user_id is checked so nobody can mark someone else's notifications
         sqlx::query(
             r#"
-            DELETE FROM admins WHERE admin_user_id=$1
+            UPDATE notifications SET is_read = true WHERE id = $1 AND user_id = $2
             "#,
         )
+        .bind(notification_id.0)
         .bind(user_id)
         .execute(&self.conn_pool)
         .await?;
@@ -481,9 +1267,1030 @@ WHERE post_id = $1;
         Ok(())
     }
 
+    pub async fn mark_all_notifications_read(&self, user_id: i32) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE notifications SET is_read = true WHERE user_id = $1 AND is_read = false
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Audit Log -------------------------------------------------------------------------------------------------------
+    /// Newest first. A `page_size` of `None` returns every matching entry, for exports.
+    pub async fn get_audit_log(
+        &self,
+        query: &AuditQuery,
+        page_size: Option<i64>,
+    ) -> Result<Vec<AuditEntry>, AppError> {
+        let offset = page_size.unwrap_or(0) * query.page.unwrap_or(0).max(0);
+        let res = sqlx::query(
+            r#"
+            SELECT * FROM audit_log
+            WHERE ($1::text IS NULL OR actor_email ILIKE '%' || $1 || '%')
+            AND ($2::text IS NULL OR action = $2)
+            AND ($3::text IS NULL OR target ILIKE '%' || $3 || '%')
+            ORDER BY created_on DESC, id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+        )
+        .bind(query.actor())
+        .bind(query.action())
+        .bind(query.target())
+        .bind(page_size)
+        .bind(offset)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let entries: Vec<_> = res
+            .into_iter()
+            .map(|row| AuditEntry {
+                id: AuditEntryId(row.get("id")),
+                actor_id: row.get("actor_id"),
+                actor_email: row.get("actor_email"),
+                action: row.get("action"),
+                target: row.get("target"),
+                payload: row.get("payload"),
+                created_on: row.get("created_on"),
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    // Reports ---------------------------------------------------------------------------------------------------------
+    pub async fn create_report(
+        &self,
+        reporter_id: i32,
+        target_kind: ReportTarget,
+        target_id: i32,
+        reason: String,
+    ) -> Result<ReportId, AppError> {
+        let res = sqlx::query(
+            r#"
+            INSERT INTO reports (reporter_id, target_kind, target_id, reason)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(reporter_id)
+        .bind(target_kind.to_string())
+        .bind(target_id)
+        .bind(reason)
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(ReportId(res.get("id")))
+    }
+
+    pub async fn get_report_by_id(&self, report_id: ReportId) -> Result<Report, AppError> {
+        let res = sqlx::query(&format!("{} WHERE reports.id = $1", REPORT_SELECT))
+            .bind(report_id.0)
+            .fetch_optional(&self.conn_pool)
+            .await?
+            .ok_or(AppError::ReportNotFound)?;
+
+        Ok(report_from_row(&res))
+    }
+
+    /// Open reports oldest first, so the queue is worked in order
+    pub async fn get_open_reports(&self) -> Result<Vec<Report>, AppError> {
+        let res = sqlx::query(&format!(
+            "{} WHERE reports.resolved_on IS NULL ORDER BY reports.created_on, reports.id",
+            REPORT_SELECT
+        ))
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(res.iter().map(report_from_row).collect())
+    }
+
+    pub async fn get_resolved_reports(&self, limit: i64) -> Result<Vec<Report>, AppError> {
+        let res = sqlx::query(&format!(
+            "{} WHERE reports.resolved_on IS NOT NULL ORDER BY reports.resolved_on DESC LIMIT $1",
+            REPORT_SELECT
+        ))
+        .bind(limit)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(res.iter().map(report_from_row).collect())
+    }
+
+    /// Carries out `resolution` on whatever `report` is about and closes every open report about the
+    /// same thing, not just the one the moderator clicked on. All of it happens in one transaction,
+    /// and if somebody else resolved the report first nothing does, with `ReportAlreadyResolved`.
+    pub async fn resolve_report(
+        &self,
+        actor_email: String,
+        actor_role: Role,
+        report: &Report,
+        resolution: ReportResolution,
+        note: String,
+    ) -> Result<(), AppError> {
+        let target_kind: ReportTarget = report
+            .target_kind
+            .parse()
+            .map_err(|_| AppError::InternalServerError)?;
+
+        let mut tx = self.conn_pool.begin().await?;
+
+        // Banning takes the role lock, which always has to come before any rows
+        if resolution == ReportResolution::AuthorBanned {
+            lock_roles(&mut tx).await?;
+        }
+
+        // Locks every open report about the target, a second moderator waits here and then finds
+        // them resolved
+        let resolved_by = find_user_id(&mut tx, &actor_email).await?;
+        let resolved: Vec<i32> = sqlx::query(
+            r#"
+            UPDATE reports
+            SET resolved_by = $3, resolved_on = NOW(), resolution = $4, resolution_note = $5
+            WHERE target_kind = $1 AND target_id = $2 AND resolved_on IS NULL
+            RETURNING id
+            "#,
+        )
+        .bind(target_kind.to_string())
+        .bind(report.target_id)
+        .bind(resolved_by)
+        .bind(resolution.to_string())
+        .bind(&note)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.get("id"))
+        .collect();
+        if !resolved.contains(&report.id.0) {
+            return Err(AppError::ReportAlreadyResolved);
+        }
+
+        match (resolution, target_kind) {
+            (ReportResolution::Dismissed, _) => {}
+            (ReportResolution::ContentDeleted, ReportTarget::Post) => {
+                delete_post(&mut tx, &actor_email, report.target_id, Some(report.id)).await?;
+            }
+            (ReportResolution::AuthorBanned, ReportTarget::User) => {
+                let email: String = sqlx::query(
+                    r#"
+                    SELECT email FROM users WHERE id = $1
+                    "#,
+                )
+                .bind(report.target_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(AppError::UserNotFound)?
+                .get("email");
+                let reason = if note.is_empty() {
+                    report.reason.clone()
+                } else {
+                    note.clone()
+                };
+                ban_user(
+                    &mut tx,
+                    &actor_email,
+                    Some(actor_role),
+                    &email,
+                    reason,
+                    None,
+                )
+                .await?;
+            }
+            // Posts are APODs with no author, and users aren't content that can be deleted
+            _ => return Err(AppError::InvalidResolution),
+        }
+
+        record_audit(
+            &mut tx,
+            &actor_email,
+            AuditAction::ResolveReport,
+            &format!("{} {}", target_kind, report.target_id),
+            json!({
+                "report_id": report.id,
+                "resolution": resolution,
+                "note": note,
+            }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Stats -----------------------------------------------------------------------------------------------------------
+    pub async fn record_nasa_fetch(
+        &self,
+        query_string: String,
+        error: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO nasa_fetches (query_string, succeeded, error)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(query_string)
+        .bind(error.is_none())
+        .bind(error)
+        .execute(&self.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_site_stats(&self, days: i32) -> Result<SiteStats, AppError> {
+        let totals = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM users) AS total_users,
+                (SELECT COUNT(DISTINCT user_id) FROM bans
+                    WHERE lifted_on IS NULL AND (expires_on IS NULL OR expires_on > NOW())) AS banned_users,
+                (SELECT COUNT(*) FROM users WHERE role IN ('admin', 'owner')) AS admins,
+                (SELECT COUNT(*) FROM posts) AS total_posts,
+                (SELECT COUNT(*) FROM votes) AS total_votes,
+                (SELECT COUNT(*) FROM nasa_fetches WHERE succeeded) AS nasa_succeeded,
+                (SELECT COUNT(*) FROM nasa_fetches WHERE NOT succeeded) AS nasa_failed
+            "#,
+        )
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        let daily_signups = self.get_daily_counts("users", days).await?;
+        let daily_votes = self.get_daily_counts("votes", days).await?;
+
+        let most_active_users = sqlx::query(
+            r#"
+            SELECT users.email, COUNT(*) AS votes
+            FROM votes
+            JOIN users ON users.id = votes.user_id
+            WHERE votes.created_on > NOW() - make_interval(days => $1)
+            GROUP BY users.email
+            ORDER BY votes DESC, users.email
+            LIMIT $2
+            "#,
+        )
+        .bind(days)
+        .bind(MOST_ACTIVE_LIMIT)
+        .fetch_all(&self.conn_pool)
+        .await?
+        .iter()
+        .map(|row| ActiveUser {
+            email: row.get("email"),
+            votes: row.get("votes"),
+        })
+        .collect();
+
+        Ok(SiteStats {
+            total_users: totals.get("total_users"),
+            banned_users: totals.get("banned_users"),
+            admins: totals.get("admins"),
+            total_posts: totals.get("total_posts"),
+            total_votes: totals.get("total_votes"),
+            daily_signups,
+            daily_votes,
+            most_active_users,
+            nasa_fetches: NasaFetchCounts {
+                succeeded: totals.get("nasa_succeeded"),
+                failed: totals.get("nasa_failed"),
+            },
+        })
+    }
+
+    /// Rows per UTC day in `table` over the last `days` days, counting today. `table` is always one
+    /// of ours with a `created_on` column, never user input.
+    async fn get_daily_counts(
+        &self,
+        table: &'static str,
+        days: i32,
+    ) -> Result<Vec<DailyCount>, AppError> {
+        let res = sqlx::query(&format!(
+            r#"
+            SELECT day::date AS day, COUNT({table}.id) AS count
+            FROM generate_series(
+                (NOW() AT TIME ZONE 'UTC')::date - ($1 - 1),
+                (NOW() AT TIME ZONE 'UTC')::date,
+                '1 day'
+            ) AS day
+            LEFT JOIN {table} ON ({table}.created_on AT TIME ZONE 'UTC')::date = day::date
+            GROUP BY day
+            ORDER BY day
+            "#
+        ))
+        .bind(days)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(res
+            .iter()
+            .map(|row| DailyCount {
+                day: row.get("day"),
+                count: row.get("count"),
+            })
+            .collect())
+    }
+
+    // Admin -----------------------------------------------------------------------------------------------------------
+    // These take the role the actor was checked with and check it against the target's again once the
+    // target's row is locked, so a promotion that lands in between can't be acted on by someone below it
+    /// Bans a user, replacing any ban they already had. `expires_on` of `None` bans them until an
+    /// admin lifts it. The ban, its audit entry and the user's notification are written together.
+    pub async fn ban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email_to_ban: String,
+        reason: String,
+        expires_on: Option<DateTime<Utc>>,
+    ) -> Result<Ban, AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+        ban_user(
+            &mut tx,
+            &actor_email,
+            actor_role,
+            &email_to_ban,
+            reason,
+            expires_on,
+        )
+        .await?;
+        tx.commit().await?;
+
+        let ban = self.get_active_ban_by_email(email_to_ban).await?;
+        ban.ok_or(AppError::InternalServerError)
+    }
+
+    pub async fn unban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email_to_unban: String,
+    ) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let (user_id, role) = lock_user_role(&mut tx, &email_to_unban).await?;
+        if !role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        lift_bans(&mut tx, user_id).await?;
+        record_audit(
+            &mut tx,
+            &actor_email,
+            AuditAction::UnbanUser,
+            &email_to_unban,
+            json!({}),
+        )
+        .await?;
+        insert_notification(
+            &mut tx,
+            user_id,
+            NotificationKind::Unbanned,
+            "An admin has lifted the ban on your account".to_string(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_active_ban_by_email(&self, email: String) -> Result<Option<Ban>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT bans.*, issuer.email AS issued_by_email,
+                (bans.lifted_on IS NULL AND (bans.expires_on IS NULL OR bans.expires_on > NOW()))
+                AS is_active
+            FROM bans
+            INNER JOIN users ON users.id = bans.user_id
+            LEFT JOIN users issuer ON issuer.id = bans.issued_by
+            WHERE users.email = $1
+            AND bans.lifted_on IS NULL
+            AND (bans.expires_on IS NULL OR bans.expires_on > NOW())
+            ORDER BY bans.started_on DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        Ok(res.map(|row| ban_from_row(&row)))
+    }
+
+    pub async fn get_ban_history_by_email(&self, email: String) -> Result<Vec<Ban>, AppError> {
+        let res = sqlx::query(
+            r#"
+            SELECT bans.*, issuer.email AS issued_by_email,
+                (bans.lifted_on IS NULL AND (bans.expires_on IS NULL OR bans.expires_on > NOW()))
+                AS is_active
+            FROM bans
+            INNER JOIN users ON users.id = bans.user_id
+            LEFT JOIN users issuer ON issuer.id = bans.issued_by
+            WHERE users.email = $1
+            ORDER BY bans.started_on DESC, bans.id DESC
+            "#,
+        )
+        .bind(email)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let bans: Vec<_> = res.iter().map(ban_from_row).collect();
+
+        Ok(bans)
+    }
+
+    pub async fn promote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email_to_promote: String,
+    ) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let (user_id, role) = lock_user_role(&mut tx, &email_to_promote).await?;
+        if !role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if role.is_admin() {
+            return Err(AppError::AlreadyAdmin);
+        }
+        update_user_role(&mut tx, user_id, Role::Admin).await?;
+        record_audit(
+            &mut tx,
+            &actor_email,
+            AuditAction::PromoteAdmin,
+            &email_to_promote,
+            json!({}),
+        )
+        .await?;
+        insert_notification(
+            &mut tx,
+            user_id,
+            NotificationKind::Promoted,
+            "You have been made an admin".to_string(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Refuses to demote the last admin, that would lock everyone out of the admin panel
+    pub async fn demote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email_to_demote: String,
+    ) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let (user_id, role) = lock_user_role(&mut tx, &email_to_demote).await?;
+        if !role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if !role.is_admin() {
+            return Err(AppError::NotAnAdmin);
+        }
+        ensure_not_last_admin(&mut tx, user_id).await?;
+        update_user_role(&mut tx, user_id, Role::User).await?;
+        record_audit(
+            &mut tx,
+            &actor_email,
+            AuditAction::DemoteAdmin,
+            &email_to_demote,
+            json!({}),
+        )
+        .await?;
+        insert_notification(
+            &mut tx,
+            user_id,
+            NotificationKind::Demoted,
+            "You are no longer an admin".to_string(),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Returns the role they had before
+    pub async fn set_user_role_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        role: Role,
+    ) -> Result<Role, AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let (user_id, old_role) = lock_user_role(&mut tx, &email).await?;
+        if !old_role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if old_role.is_admin() && !role.is_admin() {
+            ensure_not_last_admin(&mut tx, user_id).await?;
+        }
+        update_user_role(&mut tx, user_id, role).await?;
+        record_audit(
+            &mut tx,
+            &actor_email,
+            AuditAction::SetRole,
+            &email,
+            json!({ "from": old_role, "to": role }),
+        )
+        .await?;
+        let kind = if role > old_role {
+            NotificationKind::Promoted
+        } else {
+            NotificationKind::Demoted
+        };
+        insert_notification(&mut tx, user_id, kind, format!("Your role is now {}", role)).await?;
+
+        tx.commit().await?;
+
+        Ok(old_role)
+    }
+
+    /// Runs a bulk action from the user management table as `actor_email`. Everyone is done in one
+    /// transaction, audit entries and notifications included, so if any of them fails, for example
+    /// because it would remove the last admin, nobody is changed. The actor's permissions and rank
+    /// are checked again here, with every row involved locked, in case they changed since the page
+    /// was shown.
+    pub async fn bulk_update_users(
+        &self,
+        actor_email: String,
+        bulk: &BulkUserAction,
+    ) -> Result<(), AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let (actor_id, actor_role) = lock_user_role(&mut tx, &actor_email).await?;
+        if !bulk.action.is_allowed_for(actor_role) {
+            return Err(AppError::MissingPermission);
+        }
+
+        for email in &bulk.emails {
+            let (user_id, role) = lock_user_role(&mut tx, email).await?;
+            if user_id == actor_id {
+                return Err(AppError::CannotActOnYourself);
+            }
+            if role >= actor_role {
+                return Err(AppError::CannotActOnUser);
+            }
+
+            let (action, payload, notification) = match bulk.action {
+                BulkAction::Ban => {
+                    if role.is_admin() {
+                        ensure_not_last_admin(&mut tx, user_id).await?;
+                    }
+                    insert_ban(&mut tx, user_id, Some(actor_id), bulk.reason.clone(), None).await?;
+                    (
+                        AuditAction::BanUser,
+                        json!({ "reason": bulk.reason, "expires_on": null, "bulk": true }),
+                        Some((NotificationKind::Banned, ban_message(&bulk.reason, None))),
+                    )
+                }
+                BulkAction::Unban => {
+                    lift_bans(&mut tx, user_id).await?;
+                    (
+                        AuditAction::UnbanUser,
+                        json!({ "bulk": true }),
+                        Some((
+                            NotificationKind::Unbanned,
+                            "An admin has lifted the ban on your account".to_string(),
+                        )),
+                    )
+                }
+                BulkAction::Promote => {
+                    if role.is_admin() {
+                        return Err(AppError::AlreadyAdmin);
+                    }
+                    update_user_role(&mut tx, user_id, Role::Admin).await?;
+                    (
+                        AuditAction::PromoteAdmin,
+                        json!({ "bulk": true }),
+                        Some((
+                            NotificationKind::Promoted,
+                            "You have been made an admin".to_string(),
+                        )),
+                    )
+                }
+                BulkAction::Delete => {
+                    if role.is_admin() {
+                        ensure_not_last_admin(&mut tx, user_id).await?;
+                    }
+                    sqlx::query(
+                        r#"
+                        DELETE FROM users WHERE id = $1
+                        "#,
+                    )
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    (AuditAction::DeleteUser, json!({ "bulk": true }), None)
+                }
+            };
+
+            record_audit(&mut tx, &actor_email, action, email, payload).await?;
+            if let Some((kind, message)) = notification {
+                insert_notification(&mut tx, user_id, kind, message).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     // pub async fn make_user_admin()
 }
 
+async fn record_activity(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    post_id: i32,
+    kind: ActivityKind,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO activity (user_id, post_id, kind) VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(user_id)
+    .bind(post_id)
+    .bind(kind.to_string())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn delete_activity(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    post_id: i32,
+    kind: ActivityKind,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        DELETE FROM activity WHERE user_id = $1 AND post_id = $2 AND kind = $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(post_id)
+    .bind(kind.to_string())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Appends to the audit log as part of `tx`, so the entry exists exactly when the action it
+/// describes does. `actor_email` doesn't have to be a user, the CLI writes as `admin-cli`.
+async fn record_audit(
+    tx: &mut Transaction<'_, Postgres>,
+    actor_email: &str,
+    action: AuditAction,
+    target: &str,
+    payload: Value,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (actor_id, actor_email, action, target, payload)
+        VALUES ((SELECT id FROM users WHERE email = $1), $1, $2, $3, $4)
+        "#,
+    )
+    .bind(actor_email)
+    .bind(action.to_string())
+    .bind(target)
+    .bind(payload)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes a post and records it as `actor_email`, with the report that led to it if there was one
+async fn delete_post(
+    tx: &mut Transaction<'_, Postgres>,
+    actor_email: &str,
+    post_id: i32,
+    report_id: Option<ReportId>,
+) -> Result<(), AppError> {
+    let res = sqlx::query(
+        r#"
+        DELETE FROM posts WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(post_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let old_post = Post {
+        id: PostId(res.get("id")),
+        title: res.get("title"),
+        query_string: res.get("query_string"),
+        explanation: res.get("explanation"),
+        img_url: res.get("img_url"),
+        apod_date: res.get("apod_date"),
+    };
+    let payload = match report_id {
+        Some(report_id) => json!({ "post": old_post, "report_id": report_id }),
+        None => json!({ "post": old_post }),
+    };
+    record_audit(
+        tx,
+        actor_email,
+        AuditAction::DeletePost,
+        &format!("post {}", post_id),
+        payload,
+    )
+    .await
+}
+
+async fn insert_notification(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    kind: NotificationKind,
+    message: String,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO notifications (user_id, kind, message) VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(user_id)
+    .bind(kind.to_string())
+    .bind(message)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+fn ban_message(reason: &str, expires_on: Option<DateTime<Utc>>) -> String {
+    match expires_on {
+        Some(expires_on) => format!(
+            "An admin has banned your account until {}: {}",
+            expires_on.format("%Y-%m-%d"),
+            reason
+        ),
+        None => format!("An admin has banned your account: {}", reason),
+    }
+}
+
+/// `None` for actors that aren't users, like the CLI
+async fn find_user_id(
+    tx: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<Option<i32>, AppError> {
+    let res = sqlx::query(
+        r#"
+        SELECT id FROM users WHERE email = $1
+        "#,
+    )
+    .bind(email)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(res.map(|row| row.get("id")))
+}
+
+/// Bans a user as `actor_email` with the audit entry and their notification, see `ban_user_by_email`
+async fn ban_user(
+    tx: &mut Transaction<'_, Postgres>,
+    actor_email: &str,
+    actor_role: Option<Role>,
+    email_to_ban: &str,
+    reason: String,
+    expires_on: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let issued_by = find_user_id(tx, actor_email).await?;
+    let (user_id, role) = lock_user_role(tx, email_to_ban).await?;
+    if issued_by == Some(user_id) {
+        return Err(AppError::CannotActOnYourself);
+    }
+    if !role.is_outranked_by(actor_role) {
+        return Err(AppError::CannotActOnUser);
+    }
+    if role.is_admin() {
+        ensure_not_last_admin(tx, user_id).await?;
+    }
+
+    insert_ban(tx, user_id, issued_by, reason.clone(), expires_on).await?;
+    record_audit(
+        tx,
+        actor_email,
+        AuditAction::BanUser,
+        email_to_ban,
+        json!({ "reason": reason, "expires_on": expires_on }),
+    )
+    .await?;
+    insert_notification(
+        tx,
+        user_id,
+        NotificationKind::Banned,
+        ban_message(&reason, expires_on),
+    )
+    .await
+}
+
+/// Lifts whatever ban the user has now, then bans them again with the new details
+async fn insert_ban(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    issued_by: Option<i32>,
+    reason: String,
+    expires_on: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    lift_bans(tx, user_id).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO bans (user_id, issued_by, reason, expires_on) VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(user_id)
+    .bind(issued_by)
+    .bind(reason)
+    .bind(expires_on)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Lifts the bans that are still in force. Ones that already ran out keep no `lifted_on`, so the
+/// history shows they expired rather than being lifted.
+async fn lift_bans(tx: &mut Transaction<'_, Postgres>, user_id: i32) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE bans SET lifted_on = NOW()
+        WHERE user_id = $1 AND lifted_on IS NULL
+            AND (expires_on IS NULL OR expires_on > NOW())
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Looks up a user's id and role, locking their row until the transaction ends. Takes the role lock
+/// before the row, so every transaction that changes roles or bans queues up in the same order.
+async fn lock_user_role(
+    tx: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<(i32, Role), AppError> {
+    lock_roles(tx).await?;
+
+    let res = sqlx::query(
+        r#"
+        SELECT id, role FROM users WHERE email = $1 FOR UPDATE
+        "#,
+    )
+    .bind(email)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+
+    let role: String = res.get("role");
+    let role = role.parse().map_err(|_| AppError::InternalServerError)?;
+
+    Ok((res.get("id"), role))
+}
+
+async fn update_user_role(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    role: Role,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE users SET role = $1 WHERE id = $2
+        "#,
+    )
+    .bind(role.to_string())
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Serializes every transaction that could take away someone's admin rights, until it ends. One
+/// advisory lock rather than a row lock per admin, so two admins demoting or banning each other at
+/// the same time wait for one another instead of deadlocking, and the second one sees `LastAdmin`.
+async fn lock_roles(tx: &mut Transaction<'_, Postgres>) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        SELECT pg_advisory_xact_lock($1)
+        "#,
+    )
+    .bind(ROLE_LOCK_KEY)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Errors if `user_id` is the only admin left who isn't banned. Only meaningful while holding
+/// `lock_roles`, which `lock_user_role` takes.
+async fn ensure_not_last_admin(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+) -> Result<(), AppError> {
+    let res = sqlx::query(
+        r#"
+        SELECT COUNT(*) AS other_admins FROM users
+        WHERE role IN ('admin', 'owner')
+        AND id <> $1
+        AND NOT EXISTS (
+            SELECT * FROM bans
+            WHERE bans.user_id = users.id
+            AND bans.lifted_on IS NULL
+            AND (bans.expires_on IS NULL OR bans.expires_on > NOW())
+        )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let other_admins: i64 = res.get("other_admins");
+    if other_admins == 0 {
+        Err(AppError::LastAdmin)
+    } else {
+        Ok(())
+    }
+}
+
+fn user_list_entry_from_row(row: &PgRow) -> Result<UserListEntry, AppError> {
+    let role: String = row.get("role");
+    Ok(UserListEntry {
+        id: row.get("id"),
+        email: row.get("email"),
+        role: role.parse().map_err(|_| AppError::InternalServerError)?,
+        created_on: row.get("created_on"),
+        last_login_on: row.get("last_login_on"),
+        is_banned: row.get("is_banned"),
+        vote_count: row.get("vote_count"),
+    })
+}
+
+fn revision_from_row(row: &PgRow) -> PostRevision {
+    PostRevision {
+        id: PostRevisionId(row.get("id")),
+        post_id: PostId(row.get("post_id")),
+        editor_id: row.get("editor_id"),
+        editor_email: row.get("editor_email"),
+        source: row.get("source"),
+        title: row.get("title"),
+        explanation: row.get("explanation"),
+        img_url: row.get("img_url"),
+        apod_date: row.get("apod_date"),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn report_from_row(row: &PgRow) -> Report {
+    Report {
+        id: ReportId(row.get("id")),
+        reporter_id: row.get("reporter_id"),
+        reporter_email: row.get("reporter_email"),
+        target_kind: row.get("target_kind"),
+        target_id: row.get("target_id"),
+        target_label: row.get("target_label"),
+        reason: row.get("reason"),
+        created_on: row.get("created_on"),
+        resolved_by: row.get("resolved_by"),
+        resolved_by_email: row.get("resolved_by_email"),
+        resolved_on: row.get("resolved_on"),
+        resolution: row.get("resolution"),
+        resolution_note: row.get("resolution_note"),
+    }
+}
+
+fn ban_from_row(row: &PgRow) -> Ban {
+    Ban {
+        id: BanId(row.get("id")),
+        user_id: row.get("user_id"),
+        issued_by: row.get("issued_by"),
+        issued_by_email: row.get("issued_by_email"),
+        reason: row.get("reason"),
+        started_on: row.get("started_on"),
+        expires_on: row.get("expires_on"),
+        lifted_on: row.get("lifted_on"),
+        is_active: row.get("is_active"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
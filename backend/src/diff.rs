@@ -0,0 +1,136 @@
+use crate::models::revision::{DiffChunk, FieldDiff};
+
+/// Word by word diff of two versions of a field, using the longest common subsequence of their
+/// words. Whitespace sticks to the word before it so the chunks join back into the original text.
+pub fn diff_field(field: &str, old: &str, new: &str) -> FieldDiff {
+    let old_words: Vec<&str> = old.split_inclusive(char::is_whitespace).collect();
+    let new_words: Vec<&str> = new.split_inclusive(char::is_whitespace).collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old_words[i..] and new_words[j..]
+    let mut lcs = vec![vec![0usize; new_words.len() + 1]; old_words.len() + 1];
+    for i in (0..old_words.len()).rev() {
+        for j in (0..new_words.len()).rev() {
+            lcs[i][j] = if old_words[i] == new_words[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut chunks: Vec<DiffChunk> = Vec::new();
+    let mut push = |kind: &str, word: &str| match chunks.last_mut() {
+        Some(last) if last.kind == kind => last.text.push_str(word),
+        _ => chunks.push(DiffChunk {
+            kind: kind.to_string(),
+            text: word.to_string(),
+        }),
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_words.len() && j < new_words.len() {
+        if old_words[i] == new_words[j] {
+            push("same", old_words[i]);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            push("removed", old_words[i]);
+            i += 1;
+        } else {
+            push("added", new_words[j]);
+            j += 1;
+        }
+    }
+    for word in &old_words[i..] {
+        push("removed", word);
+    }
+    for word in &new_words[j..] {
+        push("added", word);
+    }
+
+    FieldDiff {
+        field: field.to_string(),
+        changed: old != new,
+        chunks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(old: &str, new: &str) -> Vec<(String, String)> {
+        diff_field("title", old, new)
+            .chunks
+            .into_iter()
+            .map(|chunk| (chunk.kind, chunk.text))
+            .collect()
+    }
+
+    fn chunk(kind: &str, text: &str) -> (String, String) {
+        (kind.to_string(), text.to_string())
+    }
+
+    #[test]
+    fn unchanged_text_is_one_same_chunk() {
+        let diff = diff_field(
+            "title",
+            "The Pillars of Creation",
+            "The Pillars of Creation",
+        );
+        assert!(!diff.changed);
+        assert_eq!(
+            chunks("The Pillars of Creation", "The Pillars of Creation"),
+            [chunk("same", "The Pillars of Creation")]
+        );
+    }
+
+    #[test]
+    fn a_replaced_word_is_removed_then_added() {
+        assert_eq!(
+            chunks("The Horsehead Nebula in Orion", "The Flame Nebula in Orion"),
+            [
+                chunk("same", "The "),
+                chunk("removed", "Horsehead "),
+                chunk("added", "Flame "),
+                chunk("same", "Nebula in Orion"),
+            ]
+        );
+    }
+
+    #[test]
+    fn words_outside_the_common_subsequence_are_kept_in_order() {
+        assert_eq!(
+            chunks("a b c d", "b c e"),
+            [
+                chunk("removed", "a "),
+                chunk("same", "b c "),
+                chunk("removed", "d"),
+                chunk("added", "e"),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunks_join_back_into_both_versions() {
+        let (old, new) = ("M31  and\nM33 ", "M31 and M110\n");
+        let diff = diff_field("explanation", old, new);
+        assert!(diff.changed);
+        let rebuild = |skip: &str| -> String {
+            diff.chunks
+                .iter()
+                .filter(|chunk| chunk.kind != skip)
+                .map(|chunk| chunk.text.as_str())
+                .collect()
+        };
+        assert_eq!(rebuild("added"), old);
+        assert_eq!(rebuild("removed"), new);
+    }
+
+    #[test]
+    fn empty_sides() {
+        assert_eq!(chunks("", "New"), [chunk("added", "New")]);
+        assert_eq!(chunks("Old", ""), [chunk("removed", "Old")]);
+        assert!(chunks("", "").is_empty());
+    }
+}
@@ -1,67 +1,497 @@
-use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
 use axum::Json;
-use http::StatusCode;
+use http::header::{ACCEPT, HOST, REFERER};
+use http::{HeaderMap, Method, Request, StatusCode, Uri};
+use serde::Serialize;
 use serde_json::json;
+use sqlx::error::ErrorKind;
 use sqlx::Error;
+use tera::Context;
+use tracing::{debug, error};
+
+use crate::config::Config;
+use crate::flash::{redirect_with_flash, Flash};
+use crate::template::TEMPLATES;
 
 #[derive(Debug)]
 pub enum AppError {
+    /// Anything from the database that isn't mapped to one of the variants below, never shown to users
     Database(sqlx::Error),
+    /// Something that doesn't exist, when there's no more specific variant like `PostNotFound`
+    NotFound,
+    /// The request clashes with what's already there, e.g. a unique constraint
+    Conflict(String),
+    Forbidden(String),
+    /// Every field that didn't pass, so a form can show them all at once
+    Validation(Vec<FieldError>),
+    /// A service we depend on (like NASA) failed or answered with something we can't use
+    Upstream(&'static str),
     MissingCredentials,
     InvalidPassword,
     UserDoesNotExist,
     UserAlreadyExists,
     InvalidToken,
     InternalServerError,
-    NASAError,
     InvalidDateRange,
+    InvalidBanExpiry,
+    MissingPermission,
+    CannotActOnUser,
+    CannotActOnYourself,
+    UserNotFound,
+    AlreadyAdmin,
+    NotAnAdmin,
+    LastAdmin,
+    ReportNotFound,
+    ReportAlreadyResolved,
+    InvalidResolution,
+    InvalidBulkAction,
+    PostNotFound,
+    RevisionNotFound,
     #[allow(dead_code)]
     Any(anyhow::Error),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::Database(err) => (StatusCode::SERVICE_UNAVAILABLE, err.to_string()),
-            AppError::Any(err) => {
-                let message = format!("Internal server error! {}", err);
-                (StatusCode::INTERNAL_SERVER_ERROR, message)
-            }
+/// One field that failed validation and why
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl AppError {
+    /// The status, a stable machine readable code and a message that's safe to show to anyone.
+    /// Codes are part of the API, so change messages freely but never codes.
+    fn parts(&self) -> (StatusCode, &'static str, String) {
+        match self {
+            AppError::Database(err) => match err {
+                Error::PoolTimedOut | Error::PoolClosed | Error::Io(_) => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "database_unavailable",
+                    "The database isn't available right now, try again in a moment".to_string(),
+                ),
+                _ => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "database_error",
+                    "Something went wrong talking to the database".to_string(),
+                ),
+            },
+            AppError::Any(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Internal server error!".to_string(),
+            ),
+            AppError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "not_found",
+                "That doesn't exist".to_string(),
+            ),
+            AppError::Conflict(message) => (StatusCode::CONFLICT, "conflict", message.clone()),
+            AppError::Forbidden(message) => (StatusCode::FORBIDDEN, "forbidden", message.clone()),
+            AppError::Validation(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation_failed",
+                "Some of the fields need another look".to_string(),
+            ),
+            AppError::Upstream(service) => (
+                StatusCode::BAD_GATEWAY,
+                "upstream_error",
+                format!("{} didn't answer properly, try again later", service),
+            ),
             AppError::MissingCredentials => (
                 StatusCode::UNAUTHORIZED,
+                "missing_credentials",
                 "Your credentials were missing or otherwise incorrect".to_string(),
             ),
             AppError::UserDoesNotExist => (
                 StatusCode::UNAUTHORIZED,
+                "user_does_not_exist",
                 "Your account does not exist!".to_string(),
             ),
             AppError::UserAlreadyExists => (
-                StatusCode::UNAUTHORIZED,
+                StatusCode::CONFLICT,
+                "user_already_exists",
                 "There is already an account with that email address in the system".to_string(),
             ),
-            AppError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid Token".to_string()),
-            AppError::InvalidPassword => (StatusCode::UNAUTHORIZED, "Invalid Password".to_string()),
+            AppError::InvalidToken => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_token",
+                "Invalid Token".to_string(),
+            ),
+            AppError::InvalidPassword => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_password",
+                "Invalid Password".to_string(),
+            ),
             AppError::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
                 "Something terrible happened".to_string(),
             ),
-            AppError::NASAError => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Something terrible happened with NASA".to_string(),
-            ),
             AppError::InvalidDateRange => (
                 StatusCode::BAD_REQUEST,
+                "invalid_date_range",
                 "You used a value outside the legal date-range for NASA".to_string(),
             ),
+            AppError::InvalidBanExpiry => (
+                StatusCode::BAD_REQUEST,
+                "invalid_ban_expiry",
+                "A ban has to end on a valid date in the future".to_string(),
+            ),
+            AppError::MissingPermission => (
+                StatusCode::FORBIDDEN,
+                "missing_permission",
+                "Your role doesn't allow you to do that".to_string(),
+            ),
+            AppError::CannotActOnUser => (
+                StatusCode::FORBIDDEN,
+                "cannot_act_on_user",
+                "You can only act on users, and hand out roles, below your own role".to_string(),
+            ),
+            AppError::CannotActOnYourself => (
+                StatusCode::CONFLICT,
+                "cannot_act_on_yourself",
+                "You can't do that to your own account".to_string(),
+            ),
+            AppError::UserNotFound => (
+                StatusCode::NOT_FOUND,
+                "user_not_found",
+                "There is no account with that email address".to_string(),
+            ),
+            AppError::AlreadyAdmin => (
+                StatusCode::CONFLICT,
+                "already_admin",
+                "That user is already an admin".to_string(),
+            ),
+            AppError::NotAnAdmin => (
+                StatusCode::CONFLICT,
+                "not_an_admin",
+                "That user isn't an admin".to_string(),
+            ),
+            AppError::LastAdmin => (
+                StatusCode::CONFLICT,
+                "last_admin",
+                "That is the last admin, make someone else an admin first".to_string(),
+            ),
+            AppError::ReportNotFound => (
+                StatusCode::NOT_FOUND,
+                "report_not_found",
+                "That report doesn't exist".to_string(),
+            ),
+            AppError::ReportAlreadyResolved => (
+                StatusCode::CONFLICT,
+                "report_already_resolved",
+                "That report has already been resolved".to_string(),
+            ),
+            AppError::InvalidResolution => (
+                StatusCode::BAD_REQUEST,
+                "invalid_resolution",
+                "That resolution doesn't apply to this kind of report".to_string(),
+            ),
+            AppError::InvalidBulkAction => (
+                StatusCode::BAD_REQUEST,
+                "invalid_bulk_action",
+                "Pick an action and at least one user".to_string(),
+            ),
+            AppError::PostNotFound => (
+                StatusCode::NOT_FOUND,
+                "post_not_found",
+                "That post doesn't exist".to_string(),
+            ),
+            AppError::RevisionNotFound => (
+                StatusCode::NOT_FOUND,
+                "revision_not_found",
+                "That post doesn't have a revision with that id".to_string(),
+            ),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, code, error_message) = self.parts();
+
+        // The details stay in the logs, tagged with the request id, instead of going to the user
+        match &self {
+            AppError::Database(err) => error!("Database error: {:?}", err),
+            AppError::Any(err) => error!("Internal error: {:?}", err),
+            _ if status.is_server_error() => error!("{}: {}", code, error_message),
+            _ => {}
+        }
+
+        let is_validation = matches!(self, AppError::Validation(_));
+        let fields = match self {
+            AppError::Validation(fields) => fields,
+            _ => Vec::new(),
         };
 
-        let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        let mut body = json!({ "error": error_message, "code": code });
+        if is_validation {
+            body["fields"] = json!(fields);
+        }
+
+        let mut response = (status, Json(body)).into_response();
+        response.extensions_mut().insert(ErrorDetails {
+            code,
+            message: error_message,
+            fields,
+        });
+        response
     }
 }
 
+/// What an error response said, kept on the response so `negotiate_errors` can show it as HTML instead
+#[derive(Clone, Debug)]
+pub struct ErrorDetails {
+    pub code: &'static str,
+    pub message: String,
+    pub fields: Vec<FieldError>,
+}
+
+/// Browsers get errors as pages rather than JSON. A form post that fails goes back to the page it came
+/// from with the error as a flash message, anything else gets the error page. JSON clients, and anything
+/// that doesn't ask for HTML, keep getting JSON.
+pub async fn negotiate_errors<B>(
+    State(config): State<Arc<Config>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let wants_html = prefers_html(request.headers());
+    let back =
+        (request.method() == Method::POST).then(|| back_to(request.uri(), request.headers()));
+
+    let response = next.run(request).await;
+    if !wants_html {
+        return response;
+    }
+    let Some(details) = response.extensions().get::<ErrorDetails>().cloned() else {
+        return response;
+    };
+
+    if let Some(back) = back {
+        let field_messages = details
+            .fields
+            .iter()
+            .map(|field| field.message.clone())
+            .collect();
+        let flash = Flash::error(details.message.clone(), field_messages);
+        if let Ok(redirect) = redirect_with_flash(&back, flash, &config) {
+            return redirect.into_response();
+        }
+    }
+
+    let status = response.status();
+    let mut context = Context::new();
+    context.insert("status", &status.as_u16());
+    context.insert("reason", &status.canonical_reason().unwrap_or_default());
+    context.insert("message", &details.message);
+    context.insert("code", &details.code);
+    context.insert("fields", &details.fields);
+    let rendered = TEMPLATES
+        .render("error.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+
+    (status, Html(rendered)).into_response()
+}
+
+/// Whether `Accept` ranks HTML above JSON. Missing or `*/*` counts as JSON, so scripts and `curl` see
+/// what they always have.
+fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let quality = |wanted: &[&str]| {
+        accept
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let media_type = params.next()?.trim();
+                if !wanted.contains(&media_type) {
+                    return None;
+                }
+                Some(
+                    params
+                        .find_map(|param| param.trim().strip_prefix("q="))
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .unwrap_or(1.0),
+                )
+            })
+            .fold(0.0, f32::max)
+    };
+
+    let html = quality(&["text/html", "application/xhtml+xml"]);
+    html > 0.0 && html >= quality(&["application/json"])
+}
+
+/// Where a failed form post goes back to. A `return_to` in the form's action URL comes first, for forms
+/// on pages that are themselves a POST response (like the bulk action confirmation), where going back to
+/// `Referer` would be a 405. Otherwise it's the page the form was posted from, or the front page if that
+/// was another site.
+fn back_to(uri: &Uri, headers: &HeaderMap) -> String {
+    if let Some(return_to) = return_to(uri) {
+        return return_to;
+    }
+
+    let host = headers.get(HOST).and_then(|value| value.to_str().ok());
+    headers
+        .get(REFERER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|referer| referer.parse::<Uri>().ok())
+        .filter(|referer| referer.authority().map(|authority| authority.as_str()) == host)
+        .and_then(|referer| referer.path_and_query().map(|path| path.to_string()))
+        .unwrap_or_else(|| "/".to_string())
+}
+
+/// The `return_to` query parameter, as long as it's a path on this site and not `//elsewhere.com`
+fn return_to(uri: &Uri) -> Option<String> {
+    Query::<HashMap<String, String>>::try_from_uri(uri)
+        .ok()?
+        .0
+        .remove("return_to")
+        .filter(|path| path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\"))
+}
+
+/// Maps the errors a query can expect to hit onto the taxonomy, everything else stays `Database`
 impl From<sqlx::Error> for AppError {
     fn from(value: Error) -> Self {
-        AppError::Database(value)
+        let kind = match &value {
+            Error::RowNotFound => return AppError::NotFound,
+            Error::Database(err) => err.kind(),
+            _ => return AppError::Database(value),
+        };
+
+        match kind {
+            ErrorKind::UniqueViolation => {
+                debug!("Unique violation: {:?}", value);
+                AppError::Conflict("That already exists".to_string())
+            }
+            ErrorKind::ForeignKeyViolation => {
+                debug!("Foreign key violation: {:?}", value);
+                AppError::Conflict(
+                    "That refers to something that doesn't exist, or is still in use".to_string(),
+                )
+            }
+            _ => AppError::Database(value),
+        }
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for AppError {
+    fn from(value: sqlx::migrate::MigrateError) -> Self {
+        AppError::Any(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(http::header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    fn accepts_html(accept: &str) -> bool {
+        prefers_html(&headers(&[(ACCEPT, accept)]))
+    }
+
+    #[test]
+    fn browsers_prefer_html() {
+        assert!(accepts_html(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        ));
+        assert!(accepts_html("application/xhtml+xml"));
+        assert!(accepts_html("text/html, application/json"));
+    }
+
+    #[test]
+    fn scripts_keep_getting_json() {
+        assert!(!prefers_html(&HeaderMap::new()));
+        assert!(!accepts_html("*/*"));
+        assert!(!accepts_html("application/json"));
+        assert!(!accepts_html("text/html;q=0.5, application/json"));
+        assert!(!accepts_html("text/html;q=0"));
+    }
+
+    fn back(uri: &str, referer: Option<&str>) -> String {
+        let mut pairs = vec![(HOST, "astro.test")];
+        pairs.extend(referer.map(|referer| (REFERER, referer)));
+        back_to(&uri.parse().unwrap(), &headers(&pairs))
+    }
+
+    #[test]
+    fn back_to_the_referring_page_on_this_site() {
+        assert_eq!(
+            back("/login", Some("http://astro.test/?tab=all")),
+            "/?tab=all"
+        );
+        assert_eq!(back("/login", Some("https://elsewhere.test/")), "/");
+        assert_eq!(back("/login", Some("not a url")), "/");
+        assert_eq!(back("/login", None), "/");
+    }
+
+    #[test]
+    fn return_to_wins_over_the_referer() {
+        let referer = Some("http://astro.test/users/bulk");
+        assert_eq!(back("/users/bulk?return_to=/users", referer), "/users");
+        assert_eq!(
+            back("/users/bulk?return_to=%2Fusers%3Fpage%3D2", referer),
+            "/users?page=2"
+        );
+    }
+
+    #[test]
+    fn return_to_has_to_stay_on_this_site() {
+        let referer = Some("http://astro.test/users");
+        for uri in [
+            "/users/bulk?return_to=https://elsewhere.test/",
+            "/users/bulk?return_to=//elsewhere.test/",
+            "/users/bulk?return_to=/%5Celsewhere.test/",
+            "/users/bulk?return_to=",
+        ] {
+            assert_eq!(back(uri, referer), "/users", "{}", uri);
+        }
+    }
+
+    #[test]
+    fn codes_and_statuses_stay_stable() {
+        let (status, code, _) =
+            AppError::Validation(vec![FieldError::new("title", "Enter a title")]).parts();
+        assert_eq!(
+            (status, code),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+        let (status, code, _) = AppError::UserAlreadyExists.parts();
+        assert_eq!(
+            (status, code),
+            (StatusCode::CONFLICT, "user_already_exists")
+        );
+        let (status, code, _) = AppError::from(Error::RowNotFound).parts();
+        assert_eq!((status, code), (StatusCode::NOT_FOUND, "not_found"));
+        let (status, code, _) = AppError::from(Error::PoolTimedOut).parts();
+        assert_eq!(
+            (status, code),
+            (StatusCode::SERVICE_UNAVAILABLE, "database_unavailable")
+        );
     }
 }
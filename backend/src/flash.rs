@@ -0,0 +1,132 @@
+//! One-off messages shown on the next page a browser loads, like "Invalid Password" after a failed login.
+//! They travel in a signed cookie, so they survive the redirect but can't be forged, and the page that
+//! shows one clears it.
+
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::response::{IntoResponseParts, Response, ResponseParts};
+use cookie::time::Duration as CookieDuration;
+use cookie::{Cookie, CookieJar, SameSite};
+use http::header::SET_COOKIE;
+use http::request::Parts;
+use http::HeaderValue;
+use hyper::Body;
+use serde_derive::{Deserialize, Serialize};
+use std::convert::Infallible;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::handlers::create_redirect_to;
+use crate::models::user::request_cookies;
+
+const FLASH_COOKIE: &str = "flash";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashKind {
+    Success,
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Flash {
+    pub kind: FlashKind,
+    pub message: String,
+    /// Extra lines under the message, e.g. one per field that failed validation
+    #[serde(default)]
+    pub details: Vec<String>,
+}
+
+impl Flash {
+    pub fn success(message: impl Into<String>) -> Self {
+        Self {
+            kind: FlashKind::Success,
+            message: message.into(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>, details: Vec<String>) -> Self {
+        Self {
+            kind: FlashKind::Error,
+            message: message.into(),
+            details,
+        }
+    }
+
+    /// The signed `Set-Cookie` value that carries this message to the next page. The JSON is
+    /// percent-encoded, cookie values can't hold its quotes, commas or spaces and a `;` would end it.
+    fn cookie(&self, config: &Config) -> HeaderValue {
+        let value = serde_json::to_string(self).unwrap_or_default();
+        let cookie = Cookie::build(FLASH_COOKIE, value)
+            .path("/")
+            .http_only(true)
+            .secure(config.cookie_secure)
+            .same_site(SameSite::Lax)
+            .finish();
+
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&config.cookie_key()).add(cookie);
+        let signed = jar
+            .get(FLASH_COOKIE)
+            .map(|cookie| cookie.encoded().to_string());
+        HeaderValue::from_str(&signed.unwrap_or_default()).unwrap()
+    }
+}
+
+/// Redirects to `location` with `flash` waiting there
+pub fn redirect_with_flash(
+    location: &str,
+    flash: Flash,
+    config: &Config,
+) -> Result<Response<Body>, AppError> {
+    let mut response = create_redirect_to(location)?;
+    response
+        .headers_mut()
+        .append(SET_COOKIE, flash.cookie(config));
+    Ok(response)
+}
+
+/// The message waiting for this request, if there is one. Return it alongside the page that shows it so
+/// the cookie gets cleared and the message isn't shown twice.
+pub struct IncomingFlash(pub Option<Flash>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IncomingFlash
+where
+    Arc<Config>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let key = Arc::<Config>::from_ref(state).cookie_key();
+        // A cookie with a bad signature is treated like no cookie at all
+        let flash = request_cookies(&parts.headers)
+            .signed(&key)
+            .get(FLASH_COOKIE)
+            .and_then(|cookie| serde_json::from_str(cookie.value()).ok());
+
+        Ok(IncomingFlash(flash))
+    }
+}
+
+impl IntoResponseParts for IncomingFlash {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        if self.0.is_some() {
+            let removal = Cookie::build(FLASH_COOKIE, "")
+                .path("/")
+                .max_age(CookieDuration::ZERO)
+                .finish();
+            res.headers_mut().append(
+                SET_COOKIE,
+                HeaderValue::from_str(&removal.to_string()).unwrap(),
+            );
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,68 @@
+use axum::extract::{Query, State};
+use axum::response::Response;
+use axum::{Form, Json};
+use hyper::Body;
+
+use crate::db::{Store, FEED_PAGE_SIZE};
+use crate::error::AppError;
+use crate::handlers::create_response_path;
+use crate::models::activity::{FeedItem, FeedQuery};
+use crate::models::notification::NotificationKind;
+use crate::models::user::{Claims, UserEmail};
+
+// Follows -------------------------------------------------------------------------------------------------------------
+pub async fn follow_user(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Form(email_to_follow): Form<UserEmail>,
+) -> Result<Response<Body>, AppError> {
+    let current_user_id = am_database
+        .get_user_id_by_email(claims.email.clone())
+        .await?;
+    let new_follow = am_database
+        .follow_user_by_email(current_user_id, email_to_follow.email)
+        .await?;
+
+    // Only tell them the first time, re-following shouldn't spam anyone
+    if let Some(follow) = new_follow {
+        am_database
+            .create_notification(
+                follow.followed_id,
+                NotificationKind::Followed,
+                format!("{} started following you", claims.email),
+            )
+            .await?;
+    }
+
+    let response = create_response_path()?;
+    Ok(response)
+}
+
+pub async fn unfollow_user(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Form(email_to_unfollow): Form<UserEmail>,
+) -> Result<Response<Body>, AppError> {
+    let current_user_id = am_database.get_user_id_by_email(claims.email).await?;
+    am_database
+        .unfollow_user_by_email(current_user_id, email_to_unfollow.email)
+        .await?;
+    let response = create_response_path()?;
+    Ok(response)
+}
+
+pub async fn get_feed(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Query(feed_query): Query<FeedQuery>,
+) -> Result<Json<Vec<FeedItem>>, AppError> {
+    let current_user_id = am_database.get_user_id_by_email(claims.email).await?;
+    let feed = am_database
+        .feed_for_user(
+            current_user_id,
+            feed_query.page.unwrap_or(0),
+            FEED_PAGE_SIZE,
+        )
+        .await?;
+    Ok(Json(feed))
+}
@@ -1,4 +1,7 @@
-use axum::extract::State;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::{Query, State};
 use axum::response::{Html, Response};
 use axum::{Form, Json};
 use http::header::LOCATION;
@@ -6,26 +9,40 @@ use http::{HeaderValue, StatusCode};
 use hyper::Body;
 use serde_json::Value;
 use tera::Context;
-use tracing::error;
+use tracing::{error, warn};
 
-use crate::db::Store;
+use crate::config::Config;
+use crate::db::{Store, FEED_PAGE_SIZE, RECOMMENDATION_LIMIT};
 use crate::error::AppError;
+use crate::flash::IncomingFlash;
+use crate::metrics::METRICS;
+use crate::models::activity::FeedQuery;
 use crate::models::displaypost::{DisplayPost, DisplayPostId};
 use crate::models::nasaquery::NasaQuery;
 use crate::models::post::{CreatePost, Post};
+use crate::models::role::{Permission, Role};
 use crate::models::user::{Claims, OptionalClaims};
+use crate::repository::Repository;
 
 use crate::template::TEMPLATES;
 
 #[allow(dead_code)]
 pub async fn root(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Store>,
     OptionalClaims(claims): OptionalClaims,
-) -> Result<Html<String>, AppError> {
+    Query(feed_query): Query<FeedQuery>,
+    flash: IncomingFlash,
+) -> Result<(IncomingFlash, Html<String>), AppError> {
     let mut context = Context::new();
+    context.insert("flash", &flash.0);
     context.insert("is_admin", &false);
     context.insert("is_banned", &false);
 
+    let tab = feed_query.tab.unwrap_or_else(|| "all".to_string());
+    let page = feed_query.page.unwrap_or(0).max(0);
+    context.insert("tab", &tab);
+    context.insert("page", &page);
+
     let template_name = if let Some(claims_data) = claims {
         context.insert("claims", &claims_data);
         context.insert("is_logged_in", &true);
@@ -34,63 +51,86 @@ pub async fn root(
             .await?;
         context.insert("current_user_id", &current_user_id);
 
+        let unread_notifications = am_database
+            .count_unread_notifications(current_user_id)
+            .await?;
+        context.insert("unread_notifications", &unread_notifications);
+
         // determine if banned
         let is_banned = am_database
             .determine_if_user_banned(claims_data.email.clone())
             .await?;
         if is_banned == true {
             context.insert("is_banned", &true);
+            let ban = am_database
+                .get_active_ban_by_email(claims_data.email.clone())
+                .await?;
+            context.insert("ban", &ban);
             "banned.html"
         } else {
-            // determine if admin
-            let is_admin = am_database
-                .determine_if_user_admin(claims_data.email)
+            // determine what the admin panel should show
+            let role = am_database
+                .get_user_role_by_email(claims_data.email)
                 .await?;
-            if is_admin == true {
-                context.insert("is_admin", &true);
-            }
+            let permissions: Vec<String> = role
+                .permissions()
+                .iter()
+                .map(|permission| permission.to_string())
+                .collect();
+            let assignable_roles: Vec<String> = Role::ALL
+                .iter()
+                .filter(|assignable| **assignable < role)
+                .map(|assignable| assignable.to_string())
+                .collect();
+            context.insert("is_admin", &role.is_admin());
+            context.insert("role", &role);
+            context.insert("permissions", &permissions);
+            context.insert("assignable_roles", &assignable_roles);
 
             // Get all the post data
             let posts = am_database.get_all_posts().await?;
+            let post_ids: Vec<i32> = posts.iter().map(|post| post.id.0).collect();
+            let mut also_liked = am_database
+                .get_similar_posts_for_posts(&post_ids, RECOMMENDATION_LIMIT)
+                .await?;
             let mut display_posts = Vec::new();
             for post in posts {
-                let num_likes = am_database.get_number_of_votes_for_post(post.id.0).await?;
-                let already_liked = am_database
-                    .determine_if_user_liked_post(current_user_id, post.id.0)
-                    .await?;
-                display_posts.push(DisplayPost {
-                    id: DisplayPostId(post.id.0),
-                    title: (post.title),
-                    query_string: (post.query_string),
-                    explanation: (post.explanation),
-                    img_url: (post.img_url),
-                    apod_date: (post.apod_date),
-                    already_liked: (already_liked),
-                    num_likes: (num_likes),
-                })
+                let mut display_post =
+                    build_display_post(&am_database, current_user_id, post).await?;
+                display_post.also_liked = also_liked.remove(&display_post.id.0).unwrap_or_default();
+                display_posts.push(display_post)
             }
 
             let top_posts = am_database.get_top_posts().await?;
             let mut top_display_posts = Vec::new();
             for post_id in top_posts {
                 let post = am_database.get_post_by_id(post_id).await?;
-                let num_likes = am_database.get_number_of_votes_for_post(post.id.0).await?;
-                let already_liked = am_database
-                    .determine_if_user_liked_post(current_user_id, post.id.0)
-                    .await?;
-                top_display_posts.push(DisplayPost {
-                    id: DisplayPostId(post.id.0),
-                    title: (post.title),
-                    query_string: (post.query_string),
-                    explanation: (post.explanation),
-                    img_url: (post.img_url),
-                    apod_date: (post.apod_date),
-                    already_liked: (already_liked),
-                    num_likes: (num_likes),
-                })
+                top_display_posts
+                    .push(build_display_post(&am_database, current_user_id, post).await?)
+            }
+
+            let recommended_posts = am_database
+                .get_recommended_posts_for_user(current_user_id, RECOMMENDATION_LIMIT)
+                .await?;
+            let mut recommended_display_posts = Vec::new();
+            for post in recommended_posts {
+                recommended_display_posts
+                    .push(build_display_post(&am_database, current_user_id, post).await?)
             }
             context.insert("all_posts", &display_posts);
             context.insert("top_posts", &top_display_posts);
+            context.insert("recommended_posts", &recommended_display_posts);
+
+            // Get the "Following" tab data
+            if tab == "following" {
+                let following = am_database.get_followed_emails(current_user_id).await?;
+                let feed = am_database
+                    .feed_for_user(current_user_id, page, FEED_PAGE_SIZE)
+                    .await?;
+                context.insert("has_next_page", &(feed.len() as i64 == FEED_PAGE_SIZE));
+                context.insert("following", &following);
+                context.insert("feed", &feed);
+            }
 
             "main.html"
         }
@@ -106,7 +146,31 @@ pub async fn root(
             error!("Template rendering error: {}", err);
             panic!()
         });
-    Ok(Html(rendered))
+    Ok((flash, Html(rendered)))
+}
+
+/// Adds the like count and whether the current user already liked it to a post
+async fn build_display_post(
+    am_database: &Store,
+    current_user_id: i32,
+    post: Post,
+) -> Result<DisplayPost, AppError> {
+    let num_likes = am_database.get_number_of_votes_for_post(post.id.0).await?;
+    let already_liked = am_database
+        .determine_if_user_liked_post(current_user_id, post.id.0)
+        .await?;
+
+    Ok(DisplayPost {
+        id: DisplayPostId(post.id.0),
+        title: post.title,
+        query_string: post.query_string,
+        explanation: post.explanation,
+        img_url: post.img_url,
+        apod_date: post.apod_date,
+        already_liked,
+        num_likes,
+        also_liked: Vec::new(),
+    })
 }
 
 pub async fn protected(claims: Claims) -> Result<String, AppError> {
@@ -116,76 +180,164 @@ pub async fn protected(claims: Claims) -> Result<String, AppError> {
     ))
 }
 
-pub fn create_response_path() -> Response<Body> {
+/// Guard for handlers that need more than a login. Returns the current user's role so callers can
+/// make further checks with it.
+pub async fn require_permission(
+    am_database: &dyn Repository,
+    claims: &Claims,
+    permission: Permission,
+) -> Result<Role, AppError> {
+    let role = am_database
+        .get_user_role_by_email(claims.email.clone())
+        .await?;
+    if role.has_permission(permission) {
+        Ok(role)
+    } else {
+        Err(AppError::MissingPermission)
+    }
+}
+
+/// Nobody gets to act on themselves, or on someone with the same or a higher role than their own
+pub async fn require_outranks(
+    am_database: &dyn Repository,
+    claims: &Claims,
+    actor_role: Role,
+    target_email: &str,
+) -> Result<(), AppError> {
+    if claims.email == target_email {
+        return Err(AppError::CannotActOnYourself);
+    }
+    let target_role = am_database
+        .get_user_role_by_email(target_email.to_string())
+        .await?;
+    if actor_role > target_role {
+        Ok(())
+    } else {
+        Err(AppError::CannotActOnUser)
+    }
+}
+
+pub fn create_response_path() -> Result<Response<Body>, AppError> {
+    create_redirect_to("/")
+}
+
+/// A redirect to `location`, which fails rather than panics if it isn't a valid header value
+pub fn create_redirect_to(location: &str) -> Result<Response<Body>, AppError> {
+    let location = HeaderValue::from_str(location).map_err(|err| {
+        error!("Invalid redirect location {:?}: {}", location, err);
+        AppError::InternalServerError
+    })?;
+
     let mut response = Response::builder()
         .status(StatusCode::FOUND)
         .body(Body::empty())
         .unwrap();
 
-    response
-        .headers_mut()
-        .insert(LOCATION, HeaderValue::from_static("/"));
+    response.headers_mut().insert(LOCATION, location);
 
-    response
+    Ok(response)
 }
 
 // NASA ----------------------------------------------------------------------------------------------------------------
 pub async fn get_nasa_post_by_form(
     State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
     Form(new_query): Form<NasaQuery>,
 ) -> Result<Response<Body>, AppError> {
+    require_permission(&am_database, &claims, Permission::IngestApod).await?;
     let query = NasaQuery {
         query_string: new_query.query_string,
     };
     let json_query = Json(query);
-    let _ = get_nasa_post(axum::extract::State(am_database), json_query).await?;
+    let _ = get_nasa_post(State(am_database), State(config), json_query).await?;
 
-    let response = create_response_path();
+    let response = create_response_path()?;
     Ok(response)
 }
 pub async fn get_nasa_post(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
     Json(query): Json<NasaQuery>,
 ) -> Result<Json<Post>, AppError> {
     // Check to see if post is already in DB
     let is_cached = am_database
         .check_cache_by_query_string(query.clone())
         .await?;
+    METRICS.record_apod_cache(is_cached);
     if is_cached == true {
         let cached_post = am_database.get_post_by_query_string(query.clone()).await?;
         return Ok(Json(cached_post));
     }
-    // Otherwise, call NASA and create a post for it
+    // Otherwise, call NASA and create a post for it, keeping track of how often that goes wrong
     else {
-        let date_value = &query.query_string;
-        let key = std::env::var("NASA_API_KEY").unwrap();
-        let query_string =
-            format!("https://api.nasa.gov/planetary/apod/?api_key={key}&date={date_value}");
-        let res = reqwest::get(&query_string)
-            .await
-            .map_err(|_| AppError::InternalServerError)?
-            .text()
-            .await
-            .map_err(|_| AppError::InternalServerError)?;
-
-        let response = serde_json::from_str::<Value>(&res).unwrap();
-
-        // deal with out of range response
-        if response["code"] == 400 {
-            return Err(AppError::InvalidDateRange);
-        }
+        let fetched = fetch_apod(&config, &query).await;
+        am_database
+            .record_nasa_fetch(
+                query.query_string.clone(),
+                fetched.as_ref().err().map(|err| format!("{:?}", err)),
+            )
+            .await?;
 
-        // .as_str().unwrap().to_string() seems really stupid but it was the only way I was able to get it
-        // to work without adding quotation marks to my fields. Or changing the post struct to use str
-        let post_to_add = CreatePost {
-            title: response["title"].as_str().unwrap().to_string(),
-            explanation: response["explanation"].as_str().unwrap().to_string(),
-            query_string: query.query_string,
-            img_url: response["url"].as_str().unwrap().to_string(),
-            apod_date: response["date"].as_str().unwrap().to_string(),
-        };
-
-        let new_post = am_database.add_post(post_to_add).await?;
+        let new_post = am_database.add_post(fetched?).await?;
         Ok(Json(new_post))
     }
 }
+
+/// Asks the APOD API for the picture of the day `query` asks for. Doesn't touch the database.
+pub async fn fetch_apod(config: &Config, query: &NasaQuery) -> Result<CreatePost, AppError> {
+    let started = Instant::now();
+    let fetched = request_apod(config, query).await;
+    METRICS.record_nasa_fetch(fetched.is_ok(), started.elapsed());
+    fetched
+}
+
+async fn request_apod(config: &Config, query: &NasaQuery) -> Result<CreatePost, AppError> {
+    let date_value = &query.query_string;
+    let base_url = config.nasa_base_url.trim_end_matches('/');
+    let res = reqwest::Client::new()
+        .get(format!("{base_url}/"))
+        .query(&[
+            ("api_key", config.nasa_api_key.as_str()),
+            ("date", date_value.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(nasa_request_error)?
+        .text()
+        .await
+        .map_err(nasa_request_error)?;
+
+    let response = serde_json::from_str::<Value>(&res).map_err(nasa_error)?;
+
+    // deal with out of range response
+    if response["code"] == 400 {
+        return Err(AppError::InvalidDateRange);
+    }
+
+    let field = |name: &str| {
+        response[name]
+            .as_str()
+            .map(|value| value.to_string())
+            .ok_or_else(|| nasa_error(format!("APOD response has no {:?}", name)))
+    };
+
+    Ok(CreatePost {
+        title: field("title")?,
+        explanation: field("explanation")?,
+        query_string: query.query_string.clone(),
+        img_url: field("url")?,
+        apod_date: field("date")?,
+    })
+}
+
+/// Logs what actually went wrong, users only get told NASA isn't cooperating
+fn nasa_error(err: impl std::fmt::Display) -> AppError {
+    warn!("APOD request failed: {}", err);
+    AppError::Upstream("NASA")
+}
+
+/// The URL carries `NASA_API_KEY`, so it's dropped before the error gets anywhere near the logs
+fn nasa_request_error(err: reqwest::Error) -> AppError {
+    nasa_error(err.without_url())
+}
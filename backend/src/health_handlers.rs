@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::Json;
+use http::header::CONTENT_TYPE;
+use http::StatusCode;
+use tracing::error;
+
+use crate::config::Config;
+use crate::db::{Store, MIGRATOR};
+use crate::metrics::METRICS;
+use crate::models::health::{Readiness, VersionInfo};
+
+// Health --------------------------------------------------------------------------------------------------------------
+/// Liveness, answers as long as the process can serve requests at all
+pub async fn healthz() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness, 503 until the database answers and the NASA client has everything it needs
+pub async fn readyz(
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+) -> (StatusCode, Json<Readiness>) {
+    let database = match am_database.ping().await {
+        Ok(()) => "ok".to_string(),
+        // The error can carry hostnames and credentials from the connection string, so it only goes in the log
+        Err(err) => {
+            error!("Readiness check couldn't reach the database: {:?}", err);
+            "unavailable".to_string()
+        }
+    };
+    let nasa = if config.nasa_api_key.trim().is_empty() {
+        "NASA_API_KEY is empty".to_string()
+    } else if let Err(err) = reqwest::Url::parse(&config.nasa_base_url) {
+        format!("NASA_BASE_URL isn't a valid URL: {}", err)
+    } else {
+        "ok".to_string()
+    };
+
+    let ready = database == "ok" && nasa == "ok";
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(Readiness {
+            ready,
+            database,
+            nasa,
+        }),
+    )
+}
+
+pub async fn version(State(am_database): State<Store>) -> Json<VersionInfo> {
+    Json(VersionInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: env!("GIT_HASH").to_string(),
+        migration: am_database.get_migration_version().await.ok().flatten(),
+        latest_migration: MIGRATOR.iter().map(|migration| migration.version).max(),
+    })
+}
+
+/// Prometheus scrapes this, see `metrics.rs` for what's in it
+pub async fn metrics(State(am_database): State<Store>) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(&am_database.conn_pool),
+    )
+}
@@ -1,11 +1,24 @@
-use http::Method;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::extract::MatchedPath;
+use futures::future::BoxFuture;
+use http::{Method, Request, Response};
+use tower::{Layer, Service};
 use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
 use tower_http::cors::{Any, CorsLayer};
-use tower_http::trace::TraceLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{MakeSpan, TraceLayer};
+use tracing::Span;
+
+use crate::metrics::METRICS;
 
 pub fn get_layers() -> (
     CorsLayer,
-    TraceLayer<SharedClassifier<ServerErrorsAsFailures>>,
+    TraceLayer<SharedClassifier<ServerErrorsAsFailures>, RequestSpan>,
+    MetricsLayer,
+    SetRequestIdLayer<MakeRequestUuid>,
+    PropagateRequestIdLayer,
 ) {
     let cors_layer = CorsLayer::new()
         .allow_origin(Any)
@@ -18,7 +31,94 @@ pub fn get_layers() -> (
             Method::OPTIONS,
         ]);
 
-    let trace_layer = TraceLayer::new_for_http();
+    let trace_layer = TraceLayer::new_for_http().make_span_with(RequestSpan);
+
+    // Keeps an `X-Request-Id` the client (or a proxy in front of us) sent, otherwise makes one up,
+    // and echoes it back on the response
+    let set_request_id_layer = SetRequestIdLayer::x_request_id(MakeRequestUuid);
+    let propagate_request_id_layer = PropagateRequestIdLayer::x_request_id();
+
+    (
+        cors_layer,
+        trace_layer,
+        MetricsLayer,
+        set_request_id_layer,
+        propagate_request_id_layer,
+    )
+}
+
+/// The span everything logged while handling a request ends up in. `user_id` starts out empty and is
+/// filled in by the `Claims` extractors once we know who's asking.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            request_id = %request_id,
+            user_id = tracing::field::Empty,
+        )
+    }
+}
+
+/// Counts and times every request for `/metrics`, labelled with the route it matched
+#[derive(Clone, Copy, Debug)]
+pub struct MetricsLayer;
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MetricsService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let method = request.method().to_string();
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let started = Instant::now();
+        let response = self.inner.call(request);
 
-    (cors_layer, trace_layer)
+        Box::pin(async move {
+            let response = response.await?;
+            METRICS.record_request(
+                &method,
+                &route,
+                response.status().as_u16(),
+                started.elapsed(),
+            );
+            Ok(response)
+        })
+    }
 }
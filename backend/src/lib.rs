@@ -1,59 +1,196 @@
-use std::net::{IpAddr, SocketAddr};
-use std::str::FromStr;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::db::new_pool;
+use crate::config::{Config, LogFormat};
+use crate::db::{new_pool, run_migrations};
 use crate::error::AppError;
+use crate::models::role::Role;
 use crate::routes::main_routes;
+use crate::tasks::Background;
 use dotenvy::dotenv;
+use tokio_util::sync::CancellationToken;
 
-use tracing::info;
+use tracing::{error, info, warn};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
+pub mod config;
 pub mod db;
 pub mod error;
+pub mod flash;
+pub mod state;
 
 pub mod admin_handlers;
+pub mod follow_handlers;
 pub mod handlers;
+pub mod health_handlers;
+pub mod live_handlers;
+pub mod notification_handlers;
 pub mod post_handlers;
+pub mod report_handlers;
+pub mod revision_handlers;
+pub mod settings_handlers;
+pub mod syndication_handlers;
 pub mod user_handlers;
 pub mod vote_handlers;
 
 pub mod layers;
+pub mod metrics;
 pub mod models;
+pub mod repository;
+pub mod seed;
 
-mod routes;
+mod diff;
+pub mod routes;
+mod syndication;
+pub mod tasks;
 mod template;
 
 pub async fn run_backend() {
     dotenv().ok();
-    init_logging();
+    let config = Config::load();
+    // A broken config still gets logged, just in the default format
+    init_logging(
+        config
+            .as_ref()
+            .map(|config| config.log_format)
+            .unwrap_or_default(),
+    );
 
-    let addr = get_host_from_env();
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let addr = SocketAddr::from((config.api_host, config.api_port));
 
-    let app = main_routes::app(new_pool().await).await;
+    let pool = new_pool(&config).await;
+    if config.run_migrations {
+        if let Err(err) = run_migrations(&pool).await {
+            error!("Couldn't run migrations: {:?}", err);
+            std::process::exit(1);
+        }
+        info!("Migrations are up to date");
+    }
+    if let Some(email) = config.initial_admin_email.clone() {
+        promote_initial_admin(&db::Store::with_pool(pool.clone()), email).await;
+    }
 
-    info!("Listening...");
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    let background = Background::default();
+    background.spawn(tasks::refresh_recommendations(
+        db::Store::with_pool(pool.clone()),
+        background.shutdown_token(),
+    ));
+    let app = main_routes::app(pool.clone(), Arc::new(config), background.shutdown_token());
 
-    axum::Server::bind(&addr)
+    let shutdown = background.shutdown_token();
+    tokio::spawn(cancel_on_signal(shutdown.clone()));
+    // Stops accepting connections once the token is cancelled, then waits for open requests
+    let server = axum::Server::bind(&addr)
         .serve(app.into_make_service())
-        .await
-        .unwrap();
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned());
+    let drain_deadline = async {
+        shutdown.cancelled().await;
+        tokio::time::sleep(shutdown_timeout).await;
+    };
+
+    info!("Listening...");
+
+    tokio::select! {
+        result = server => {
+            if let Err(err) = result {
+                error!("Server error: {:?}", err);
+            }
+        }
+        _ = drain_deadline => warn!(
+            "Requests still in flight after {:?}, dropping them",
+            shutdown_timeout
+        ),
+    }
+
+    background.stop(shutdown_timeout).await;
+    pool.close().await;
+    info!("Shut down cleanly");
 }
 
-fn get_host_from_env() -> SocketAddr {
-    let host = std::env::var("API_HOST").unwrap();
-    let api_host = IpAddr::from_str(&host).unwrap();
-    let api_port: u16 = std::env::var("API_PORT")
-        .expect("Could not find API_PORT in .env file")
-        .parse()
-        .expect("Can't create a u16 from the given API_PORT string");
+/// Cancels `shutdown` on Ctrl+C or SIGTERM, whichever comes first
+async fn cancel_on_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("Couldn't listen for Ctrl+C: {:?}", err);
+            std::future::pending::<()>().await;
+        }
+    };
 
-    SocketAddr::from((api_host, api_port))
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!("Couldn't listen for SIGTERM: {:?}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutting down, no longer accepting connections");
+    shutdown.cancel();
 }
 
-fn init_logging() {
+/// Makes `INITIAL_ADMIN_EMAIL` an owner so a fresh install has someone who can use the admin panel.
+/// They have to have signed up already, and anyone who's already an admin is left alone.
+async fn promote_initial_admin(store: &db::Store, email: String) {
+    let result = match store.get_user_role_by_email(email.clone()).await {
+        Ok(role) if role.is_admin() => return,
+        Ok(_) => store
+            .set_user_role_by_email(
+                "INITIAL_ADMIN_EMAIL".to_string(),
+                None,
+                email.clone(),
+                Role::Owner,
+            )
+            .await
+            .map(|_| ()),
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(()) => info!("Made INITIAL_ADMIN_EMAIL {} an owner", email),
+        Err(err) => warn!(
+            "Couldn't make INITIAL_ADMIN_EMAIL {} an owner: {:?}",
+            email, err
+        ),
+    }
+}
+
+fn init_logging(format: LogFormat) {
+    let (pretty, json) = match format {
+        LogFormat::Pretty => (Some(tracing_subscriber::fmt::layer()), None),
+        // The request span's fields (request_id, user_id, ...) go on every line
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(false),
+            ),
+        ),
+    };
+
     // https://github.com/tokio-rs/axum/blob/main/examples/tracing-aka-logging
     tracing_subscriber::registry()
         .with(
@@ -63,18 +200,18 @@ fn init_logging() {
                 "backend=trace,tower_http=debug,axum::rejection=trace".into()
             }),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(pretty)
+        .with(json)
         .init();
 }
 
-pub fn get_timestamp_after_8_hours() -> u64 {
+pub fn get_timestamp_after_hours(hours: u64) -> u64 {
     let now = SystemTime::now();
     let since_epoch = now
         .duration_since(UNIX_EPOCH)
         .expect("Time somehow went backwards");
-    // 8 hours later
-    let eight_hours_from_now = since_epoch + Duration::from_secs(60 * 60 * 8);
-    eight_hours_from_now.as_secs()
+    let later = since_epoch + Duration::from_secs(60 * 60 * hours);
+    later.as_secs()
 }
 
 pub type AppResult<T> = Result<T, AppError>;
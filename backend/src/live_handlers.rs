@@ -0,0 +1,40 @@
+use std::convert::Infallible;
+
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{self, Stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::db::Store;
+
+// Live Updates --------------------------------------------------------------------------------------------------------
+/// Streams vote counts and new posts to the page as Server-Sent Events, until the server shuts down
+pub async fn live_events(
+    State(am_database): State<Store>,
+    State(shutdown): State<CancellationToken>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = am_database.events.subscribe();
+
+    let stream = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(live_event) => match Event::default()
+                    .event(live_event.name())
+                    .json_data(&live_event)
+                {
+                    Ok(event) => return Some((Ok(event), receiver)),
+                    Err(err) => warn!("Could not serialize live event: {}", err),
+                },
+                // The page will catch up on the next event or reload, no need to hang up on it
+                Err(RecvError::Lagged(skipped)) => warn!("Live event stream skipped {}", skipped),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    // The browser reconnects by itself, hopefully to a server that's still running
+    .take_until(shutdown.cancelled_owned());
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
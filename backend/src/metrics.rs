@@ -0,0 +1,326 @@
+//! A small Prometheus registry for `/metrics`. Everything is recorded into `METRICS` as it happens
+//! and rendered in the text exposition format when scraped.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use sqlx::PgPool;
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// Seconds, from a quick cache hit up to a NASA request that's about to time out
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Default)]
+struct Counter {
+    values: Mutex<BTreeMap<Labels, u64>>,
+}
+
+#[derive(Clone, Default)]
+struct HistogramValue {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    values: Mutex<BTreeMap<Labels, HistogramValue>>,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    http_requests: Counter,
+    http_request_duration: Histogram,
+    http_errors: Counter,
+    nasa_requests: Counter,
+    nasa_request_duration: Histogram,
+    apod_cache: Counter,
+    signups: Counter,
+    votes: Counter,
+    bans: Counter,
+}
+
+impl Counter {
+    fn inc_by(&self, labels: Labels, by: u64) {
+        *self.values.lock().unwrap().entry(labels).or_default() += by;
+    }
+
+    fn inc(&self, labels: Labels) {
+        self.inc_by(labels, 1);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "counter");
+        for (labels, value) in self.values.lock().unwrap().iter() {
+            let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), value);
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&self, labels: Labels, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut values = self.values.lock().unwrap();
+        let value = values.entry(labels).or_default();
+        for (bucket, upper_bound) in value.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= upper_bound {
+                *bucket += 1;
+            }
+        }
+        value.sum += seconds;
+        value.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+        for (labels, value) in self.values.lock().unwrap().iter() {
+            for (bucket, upper_bound) in value.buckets.iter().zip(LATENCY_BUCKETS) {
+                let le = upper_bound.to_string();
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(labels, Some(&le)),
+                    bucket
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{} {}",
+                name,
+                format_labels(labels, Some("+Inf")),
+                value.count
+            );
+            let _ = writeln!(
+                out,
+                "{}_sum{} {}",
+                name,
+                format_labels(labels, None),
+                value.sum
+            );
+            let _ = writeln!(
+                out,
+                "{}_count{} {}",
+                name,
+                format_labels(labels, None),
+                value.count
+            );
+        }
+    }
+}
+
+impl Metrics {
+    /// `route` is the matched route pattern, e.g. `/posts/:id`, so ids don't each get their own series
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let labels = vec![("method", method.to_string()), ("route", route.to_string())];
+        self.http_request_duration.observe(labels.clone(), elapsed);
+
+        let mut with_status = labels.clone();
+        with_status.push(("status", status.to_string()));
+        self.http_requests.inc(with_status);
+
+        let class = match status {
+            400..=499 => "client",
+            500..=599 => "server",
+            _ => return,
+        };
+        let mut with_class = labels;
+        with_class.push(("class", class.to_string()));
+        self.http_errors.inc(with_class);
+    }
+
+    pub fn record_nasa_fetch(&self, succeeded: bool, elapsed: Duration) {
+        let outcome = if succeeded { "ok" } else { "error" };
+        self.nasa_requests
+            .inc(vec![("outcome", outcome.to_string())]);
+        self.nasa_request_duration.observe(Vec::new(), elapsed);
+    }
+
+    pub fn record_apod_cache(&self, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.apod_cache.inc(vec![("result", result.to_string())]);
+    }
+
+    pub fn record_signup(&self) {
+        self.signups.inc(Vec::new());
+    }
+
+    pub fn record_vote(&self, liked: bool) {
+        let action = if liked { "like" } else { "unlike" };
+        self.votes.inc(vec![("action", action.to_string())]);
+    }
+
+    pub fn record_bans(&self, count: u64) {
+        self.bans.inc_by(Vec::new(), count);
+    }
+
+    /// Everything so far, plus the pool gauges as they are right now
+    pub fn render(&self, pool: &PgPool) -> String {
+        let mut out = String::new();
+
+        self.http_requests.render(
+            &mut out,
+            "http_requests_total",
+            "Requests served, by route and status",
+        );
+        self.http_request_duration.render(
+            &mut out,
+            "http_request_duration_seconds",
+            "Time spent serving requests, by route",
+        );
+        self.http_errors.render(
+            &mut out,
+            "http_errors_total",
+            "Requests that ended in a 4xx (client) or 5xx (server) status",
+        );
+        self.nasa_requests.render(
+            &mut out,
+            "nasa_requests_total",
+            "Calls to the APOD API, by whether they returned a usable picture",
+        );
+        self.nasa_request_duration.render(
+            &mut out,
+            "nasa_request_duration_seconds",
+            "Time spent waiting for the APOD API",
+        );
+        self.apod_cache.render(
+            &mut out,
+            "apod_cache_requests_total",
+            "APOD lookups answered from the posts table (hit) or NASA (miss)",
+        );
+        self.signups
+            .render(&mut out, "signups_total", "Accounts created");
+        self.votes
+            .render(&mut out, "votes_total", "Likes and unlikes, by action");
+        self.bans.render(&mut out, "bans_total", "Users banned");
+
+        let size = pool.size() as usize;
+        let idle = pool.num_idle();
+        header(
+            &mut out,
+            "db_pool_connections",
+            "Open database connections, by whether they're in use",
+            "gauge",
+        );
+        let _ = writeln!(out, "db_pool_connections{{state=\"idle\"}} {}", idle);
+        let _ = writeln!(
+            out,
+            "db_pool_connections{{state=\"in_use\"}} {}",
+            size.saturating_sub(idle)
+        );
+        header(
+            &mut out,
+            "db_pool_max_connections",
+            "The most connections the pool will open",
+            "gauge",
+        );
+        let _ = writeln!(
+            out,
+            "db_pool_max_connections {}",
+            pool.options().get_max_connections()
+        );
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    async fn render(metrics: &Metrics) -> String {
+        // Never connects, `render` only reads the pool's counts
+        let pool = PgPoolOptions::new()
+            .max_connections(3)
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        metrics.render(&pool)
+    }
+
+    #[tokio::test]
+    async fn counters_have_one_series_per_label_set() {
+        let metrics = Metrics::default();
+        metrics.record_request("GET", "/posts/:id", 200, Duration::from_millis(3));
+        metrics.record_request("GET", "/posts/:id", 200, Duration::from_millis(3));
+        metrics.record_request("GET", "/posts/:id", 404, Duration::from_millis(3));
+        metrics.record_bans(3);
+
+        let out = render(&metrics).await;
+        assert!(out.contains("# TYPE http_requests_total counter\n"));
+        assert!(out.contains(
+            "http_requests_total{method=\"GET\",route=\"/posts/:id\",status=\"200\"} 2\n"
+        ));
+        assert!(out.contains(
+            "http_requests_total{method=\"GET\",route=\"/posts/:id\",status=\"404\"} 1\n"
+        ));
+        assert!(out.contains(
+            "http_errors_total{method=\"GET\",route=\"/posts/:id\",class=\"client\"} 1\n"
+        ));
+        assert!(out.contains("bans_total 3\n"));
+        assert!(out.contains("db_pool_max_connections 3\n"));
+    }
+
+    #[tokio::test]
+    async fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        metrics.record_nasa_fetch(true, Duration::from_millis(20));
+        metrics.record_nasa_fetch(false, Duration::from_secs(3));
+
+        let out = render(&metrics).await;
+        assert!(out.contains("nasa_request_duration_seconds_bucket{le=\"0.01\"} 0\n"));
+        assert!(out.contains("nasa_request_duration_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(out.contains("nasa_request_duration_seconds_bucket{le=\"2.5\"} 1\n"));
+        assert!(out.contains("nasa_request_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(out.contains("nasa_request_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(out.contains("nasa_request_duration_seconds_count 2\n"));
+        assert!(out.contains("nasa_requests_total{outcome=\"error\"} 1\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let labels = vec![("route", "/a\"b\\c\nd".to_string())];
+        assert_eq!(
+            format_labels(&labels, Some("0.5")),
+            "{route=\"/a\\\"b\\\\c\\nd\",le=\"0.5\"}"
+        );
+        assert_eq!(format_labels(&Vec::new(), None), "");
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::make_db_id;
+use crate::models::post::PostId;
+
+/// The kinds of events we record in the activity log. Likes are the only thing a user can do to a
+/// post right now, new kinds should be added here as the app grows.
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ActivityKind {
+    #[display(fmt = "liked")]
+    Liked,
+}
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, user_id: {}, post_id: {}, kind: {}",
+    id,
+    user_id,
+    post_id,
+    kind
+)]
+pub struct Activity {
+    pub id: ActivityId,
+    pub user_id: i32,
+    pub post_id: PostId,
+    pub kind: String,
+    pub created_on: DateTime<Utc>,
+}
+
+make_db_id!(ActivityId);
+
+/// One entry of a user's "Following" feed, the activity joined with who did it and what it was done to
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeedItem {
+    pub id: ActivityId,
+    pub actor_email: String,
+    pub kind: String,
+    pub post_id: PostId,
+    pub title: String,
+    pub img_url: String,
+    pub apod_date: String,
+    pub created_on: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FeedQuery {
+    pub tab: Option<String>,
+    pub page: Option<i64>,
+}
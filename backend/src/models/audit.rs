@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::make_db_id;
+
+/// Every privileged thing an admin can do, as it's written to the audit log
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AuditAction {
+    #[display(fmt = "ban_user")]
+    BanUser,
+    #[display(fmt = "unban_user")]
+    UnbanUser,
+    #[display(fmt = "promote_admin")]
+    PromoteAdmin,
+    #[display(fmt = "demote_admin")]
+    DemoteAdmin,
+    #[display(fmt = "set_role")]
+    SetRole,
+    #[display(fmt = "update_post")]
+    UpdatePost,
+    #[display(fmt = "delete_post")]
+    DeletePost,
+    #[display(fmt = "resolve_report")]
+    ResolveReport,
+    #[display(fmt = "delete_user")]
+    DeleteUser,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 9] = [
+        AuditAction::BanUser,
+        AuditAction::UnbanUser,
+        AuditAction::PromoteAdmin,
+        AuditAction::DemoteAdmin,
+        AuditAction::SetRole,
+        AuditAction::UpdatePost,
+        AuditAction::DeletePost,
+        AuditAction::ResolveReport,
+        AuditAction::DeleteUser,
+    ];
+}
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, actor_email: {}, action: {}, target: {}",
+    id,
+    actor_email,
+    action,
+    target
+)]
+pub struct AuditEntry {
+    pub id: AuditEntryId,
+    pub actor_id: Option<i32>,
+    pub actor_email: String,
+    pub action: String,
+    pub target: String,
+    pub payload: Value,
+    pub created_on: DateTime<Utc>,
+}
+
+make_db_id!(AuditEntryId);
+
+/// Filters for the audit page, blank form fields mean "don't filter on this"
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub page: Option<i64>,
+}
+
+impl AuditQuery {
+    pub fn actor(&self) -> Option<&str> {
+        non_empty(&self.actor)
+    }
+
+    pub fn action(&self) -> Option<&str> {
+        non_empty(&self.action)
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        non_empty(&self.target)
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::make_db_id;
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, user_id: {}, reason: {}, is_active: {}",
+    id,
+    user_id,
+    reason,
+    is_active
+)]
+pub struct Ban {
+    pub id: BanId,
+    pub user_id: i32,
+    pub issued_by: Option<i32>,
+    pub issued_by_email: Option<String>,
+    pub reason: String,
+    pub started_on: DateTime<Utc>,
+    /// `None` means the ban lasts until an admin lifts it
+    pub expires_on: Option<DateTime<Utc>>,
+    pub lifted_on: Option<DateTime<Utc>>,
+    /// Not lifted and not expired yet
+    pub is_active: bool,
+}
+
+make_db_id!(BanId);
+
+/// What the admin panel's ban form sends. `expires_on` is a `YYYY-MM-DD` date, left blank for a
+/// permanent ban.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateBan {
+    pub email: String,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub expires_on: String,
+}
@@ -1,4 +1,5 @@
 use crate::make_db_id;
+use crate::models::similarity::SimilarPost;
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
@@ -21,6 +22,8 @@ pub struct DisplayPost {
     pub apod_date: String,
     pub already_liked: bool,
     pub num_likes: i64,
+    #[sqlx(skip)]
+    pub also_liked: Vec<SimilarPost>,
 }
 
 impl DisplayPost {
@@ -44,6 +47,7 @@ impl DisplayPost {
             apod_date,
             already_liked,
             num_likes,
+            also_liked: Vec::new(),
         }
     }
 }
@@ -0,0 +1,21 @@
+use serde_derive::Serialize;
+
+use crate::models::post::{Post, PostId};
+
+/// Things that happen in the locker that open pages want to hear about without reloading
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum LiveEvent {
+    VoteCount { post_id: PostId, num_likes: i64 },
+    NewPost(Post),
+}
+
+impl LiveEvent {
+    /// The SSE event name the page listens for
+    pub fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::VoteCount { .. } => "vote_count",
+            LiveEvent::NewPost(_) => "new_post",
+        }
+    }
+}
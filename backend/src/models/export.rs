@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::models::ban::Ban;
+use crate::models::notification::Notification;
+use crate::models::post::PostId;
+use crate::models::report::Report;
+use crate::models::user::UserListEntry;
+
+/// Everything we keep about a user, for the download on their settings page
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountExport {
+    pub exported_on: DateTime<Utc>,
+    pub profile: UserListEntry,
+    pub likes: Vec<ExportedLike>,
+    pub following: Vec<String>,
+    pub followers: Vec<String>,
+    pub notifications: Vec<Notification>,
+    pub bans: Vec<Ban>,
+    pub reports: Vec<Report>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedLike {
+    pub post_id: PostId,
+    pub title: String,
+    pub apod_date: String,
+    pub liked_on: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccount {
+    pub password: String,
+}
@@ -0,0 +1,17 @@
+use crate::make_db_id;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, follower_id: {}, followed_id: {}",
+    id,
+    follower_id,
+    followed_id
+)]
+pub struct Follow {
+    pub id: FollowId,
+    pub follower_id: i32,
+    pub followed_id: i32,
+}
+
+make_db_id!(FollowId);
@@ -0,0 +1,19 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// What `/readyz` found, every check is either "ok" or what went wrong
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: String,
+    pub nasa: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub version: String,
+    pub git_hash: String,
+    /// The newest migration applied to the database, `None` if it can't be read
+    pub migration: Option<i64>,
+    /// The newest migration this build knows about, ahead of `migration` until they're applied
+    pub latest_migration: Option<i64>,
+}
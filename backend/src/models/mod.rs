@@ -1,5 +1,20 @@
+pub mod activity;
+pub mod audit;
+pub mod ban;
 pub mod displaypost;
+pub mod event;
+pub mod export;
+pub mod follow;
+pub mod health;
 pub mod nasaquery;
+pub mod notification;
 pub mod post;
+pub mod report;
+pub mod revision;
+pub mod role;
+pub mod seed;
+pub mod similarity;
+pub mod stats;
+pub mod syndication;
 pub mod user;
 pub mod vote;
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::make_db_id;
+
+/// Things that happen to a user that they should hear about next time they look at the site
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NotificationKind {
+    #[display(fmt = "followed")]
+    Followed,
+    #[display(fmt = "banned")]
+    Banned,
+    #[display(fmt = "unbanned")]
+    Unbanned,
+    #[display(fmt = "promoted")]
+    Promoted,
+    #[display(fmt = "demoted")]
+    Demoted,
+}
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, user_id: {}, kind: {}, message: {}, is_read: {}",
+    id,
+    user_id,
+    kind,
+    message,
+    is_read
+)]
+pub struct Notification {
+    pub id: NotificationId,
+    pub user_id: i32,
+    pub kind: String,
+    pub message: String,
+    pub is_read: bool,
+    pub created_on: DateTime<Utc>,
+}
+
+make_db_id!(NotificationId);
+
+/// What the page polls for to keep the unread badge up to date
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationSummary {
+    pub unread_count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct MarkNotificationRead {
+    pub id: NotificationId,
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::make_db_id;
+use crate::models::post::PostId;
+
+/// What a report is about. Comments don't exist yet, when they do they get reported here too.
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportTarget {
+    #[display(fmt = "post")]
+    Post,
+    #[display(fmt = "user")]
+    User,
+}
+
+impl FromStr for ReportTarget {
+    type Err = String;
+
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        match target {
+            "post" => Ok(ReportTarget::Post),
+            "user" => Ok(ReportTarget::User),
+            _ => Err(format!("Unknown report target: {}", target)),
+        }
+    }
+}
+
+/// How a moderator closed a report
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportResolution {
+    #[display(fmt = "dismissed")]
+    Dismissed,
+    #[display(fmt = "content_deleted")]
+    ContentDeleted,
+    #[display(fmt = "author_banned")]
+    AuthorBanned,
+}
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, target_kind: {}, target_id: {}, reason: {}",
+    id,
+    target_kind,
+    target_id,
+    reason
+)]
+pub struct Report {
+    pub id: ReportId,
+    pub reporter_id: Option<i32>,
+    pub reporter_email: Option<String>,
+    pub target_kind: String,
+    pub target_id: i32,
+    /// The post's title or the user's email, `None` once the content is gone
+    pub target_label: Option<String>,
+    pub reason: String,
+    pub created_on: DateTime<Utc>,
+    pub resolved_by: Option<i32>,
+    pub resolved_by_email: Option<String>,
+    pub resolved_on: Option<DateTime<Utc>>,
+    pub resolution: Option<String>,
+    pub resolution_note: Option<String>,
+}
+
+make_db_id!(ReportId);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportPost {
+    pub post_id: PostId,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportUser {
+    pub email: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveReport {
+    pub resolution: ReportResolution,
+    #[serde(default)]
+    pub note: String,
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::make_db_id;
+use crate::models::post::PostId;
+
+/// How a version of a post came about
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionSource {
+    /// What the post looked like before anyone edited it
+    #[display(fmt = "original")]
+    Original,
+    #[display(fmt = "edit")]
+    Edit,
+    #[display(fmt = "rollback")]
+    Rollback,
+    #[display(fmt = "nasa_sync")]
+    NasaSync,
+}
+
+#[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
+#[display(
+    fmt = "id: {}, post_id: {}, source: {}, title: {}",
+    id,
+    post_id,
+    source,
+    title
+)]
+pub struct PostRevision {
+    pub id: PostRevisionId,
+    pub post_id: PostId,
+    pub editor_id: Option<i32>,
+    pub editor_email: Option<String>,
+    pub source: String,
+    pub title: String,
+    pub explanation: String,
+    pub img_url: String,
+    pub apod_date: String,
+    pub created_on: DateTime<Utc>,
+}
+
+make_db_id!(PostRevisionId);
+
+/// The HTML edit form. The query string a post was fetched with can't be edited, re-syncing from
+/// NASA relies on it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditPost {
+    pub title: String,
+    pub explanation: String,
+    pub img_url: String,
+    pub apod_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: PostRevisionId,
+    pub to: PostRevisionId,
+}
+
+/// One field of a post compared between two revisions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub changed: bool,
+    pub chunks: Vec<DiffChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiffChunk {
+    /// `same`, `added` or `removed`
+    pub kind: String,
+    pub text: String,
+}
@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use derive_more::Display;
+use serde_derive::{Deserialize, Serialize};
+
+/// Roles are ordered, anyone can only act on users whose role is below their own
+#[derive(
+    Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[display(fmt = "user")]
+    User,
+    #[display(fmt = "moderator")]
+    Moderator,
+    #[display(fmt = "admin")]
+    Admin,
+    #[display(fmt = "owner")]
+    Owner,
+}
+
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    #[display(fmt = "ingest_apod")]
+    IngestApod,
+    #[display(fmt = "ban_users")]
+    BanUsers,
+    #[display(fmt = "delete_posts")]
+    DeletePosts,
+    #[display(fmt = "edit_posts")]
+    EditPosts,
+    #[display(fmt = "manage_roles")]
+    ManageRoles,
+    #[display(fmt = "view_audit_log")]
+    ViewAuditLog,
+    #[display(fmt = "moderate_reports")]
+    ModerateReports,
+    #[display(fmt = "view_dashboard")]
+    ViewDashboard,
+    #[display(fmt = "manage_users")]
+    ManageUsers,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::User, Role::Moderator, Role::Admin, Role::Owner];
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Role::User => &[Permission::IngestApod],
+            Role::Moderator => &[
+                Permission::IngestApod,
+                Permission::BanUsers,
+                Permission::DeletePosts,
+                Permission::ModerateReports,
+            ],
+            Role::Admin | Role::Owner => &[
+                Permission::IngestApod,
+                Permission::BanUsers,
+                Permission::DeletePosts,
+                Permission::EditPosts,
+                Permission::ManageRoles,
+                Permission::ViewAuditLog,
+                Permission::ModerateReports,
+                Permission::ViewDashboard,
+                Permission::ManageUsers,
+            ],
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+
+    /// Admins and owners are what used to be the single `admins` table
+    pub fn is_admin(&self) -> bool {
+        *self >= Role::Admin
+    }
+
+    /// The rank rule on its own, for checking again once the target's row is locked. `None` is the
+    /// admin CLI or seeding, which can act on anyone.
+    pub fn is_outranked_by(&self, actor_role: Option<Role>) -> bool {
+        actor_role.is_none_or(|actor_role| actor_role > *self)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|r| r.to_string() == role)
+            .ok_or_else(|| format!("Unknown role: {}", role))
+    }
+}
+
+/// What the admin panel's role form sends
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetRole {
+    pub email: String,
+    pub role: Role,
+}
@@ -0,0 +1,36 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::models::post::CreatePost;
+use crate::models::role::Role;
+
+/// Demo data for a fresh database, see `tests/fixtures/demo.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Fixture {
+    pub users: Vec<FixtureUser>,
+    pub posts: Vec<CreatePost>,
+    pub votes: Vec<FixtureVote>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureUser {
+    pub email: String,
+    /// Plain text, it's hashed with the configured salt like any other signup
+    pub password: String,
+    /// Everyone is a regular user unless the fixture says otherwise
+    pub role: Option<Role>,
+}
+
+/// Posts are looked up by their query string, since ids depend on what's already in the database
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureVote {
+    pub email: String,
+    pub query_string: String,
+}
+
+/// How much of the fixture was new, anything already there is left alone
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SeedSummary {
+    pub users: usize,
+    pub posts: usize,
+    pub votes: usize,
+}
@@ -0,0 +1,13 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::models::post::PostId;
+
+/// A post that people who liked some other post also liked. `score` is the cosine similarity of the
+/// two posts' voters, so 1.0 means exactly the same people liked both.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimilarPost {
+    pub id: PostId,
+    pub title: String,
+    pub img_url: String,
+    pub score: f64,
+}
@@ -0,0 +1,36 @@
+use chrono::NaiveDate;
+use serde_derive::{Deserialize, Serialize};
+
+/// Everything on the admin dashboard, also served as-is by the JSON variant
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SiteStats {
+    pub total_users: i64,
+    pub banned_users: i64,
+    pub admins: i64,
+    pub total_posts: i64,
+    pub total_votes: i64,
+    /// One entry per day, oldest first, including days where nothing happened
+    pub daily_signups: Vec<DailyCount>,
+    pub daily_votes: Vec<DailyCount>,
+    pub most_active_users: Vec<ActiveUser>,
+    pub nasa_fetches: NasaFetchCounts,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyCount {
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
+/// Users ranked by how many posts they liked over the dashboard's window
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveUser {
+    pub email: String,
+    pub votes: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NasaFetchCounts {
+    pub succeeded: i64,
+    pub failed: i64,
+}
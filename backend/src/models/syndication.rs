@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::models::post::Post;
+
+/// A post as it appears in an RSS or Atom feed. `added_on` is when it showed up in that feed, so
+/// when it was added to the locker for the main feeds and when it was liked for a user's feed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyndicationEntry {
+    pub post: Post,
+    pub added_on: DateTime<Utc>,
+}
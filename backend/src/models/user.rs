@@ -1,12 +1,18 @@
 use axum::async_trait;
-use axum::extract::FromRequestParts;
-use cookie::Cookie;
+use axum::extract::{FromRef, FromRequestParts};
+use cookie::{Cookie, CookieJar};
+use http::header::COOKIE;
 use http::request::Parts;
+use http::HeaderMap;
 use jsonwebtoken::{decode, DecodingKey, EncodingKey, Validation};
-use once_cell::sync::Lazy;
 use std::convert::Infallible;
+use std::str::FromStr;
+use std::sync::Arc;
 
+use crate::config::Config;
 use crate::error::AppError;
+use crate::models::role::{Permission, Role};
+use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, sqlx::FromRow, Clone, Debug)]
@@ -27,8 +33,113 @@ pub struct UserEmail {
     pub email: String,
 }
 
-pub struct LoggedInUser {
-    pub token: Claims,
+/// A row of the admin user management table
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserListEntry {
+    pub id: i32,
+    pub email: String,
+    pub role: Role,
+    pub created_on: DateTime<Utc>,
+    pub last_login_on: Option<DateTime<Utc>>,
+    pub is_banned: bool,
+    pub vote_count: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserListQuery {
+    pub search: Option<String>,
+    pub page: Option<i64>,
+}
+
+impl UserListQuery {
+    pub fn search(&self) -> Option<&str> {
+        self.search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, derive_more::Display)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    #[display(fmt = "ban")]
+    Ban,
+    #[display(fmt = "unban")]
+    Unban,
+    #[display(fmt = "promote")]
+    Promote,
+    #[display(fmt = "delete")]
+    Delete,
+}
+
+impl BulkAction {
+    /// Each action needs the same permission as doing it to one user, on top of `ManageUsers`
+    pub fn is_allowed_for(&self, role: Role) -> bool {
+        let allowed = match self {
+            BulkAction::Ban | BulkAction::Unban => role.has_permission(Permission::BanUsers),
+            BulkAction::Promote => role > Role::Admin,
+            BulkAction::Delete => true,
+        };
+        allowed && role.has_permission(Permission::ManageUsers)
+    }
+}
+
+impl FromStr for BulkAction {
+    type Err = AppError;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "ban" => Ok(BulkAction::Ban),
+            "unban" => Ok(BulkAction::Unban),
+            "promote" => Ok(BulkAction::Promote),
+            "delete" => Ok(BulkAction::Delete),
+            _ => Err(AppError::InvalidBulkAction),
+        }
+    }
+}
+
+/// The bulk action form. Every ticked user is its own `email` field, which `Form` can't collect into
+/// a `Vec`, so this gets built from the raw key/value pairs instead.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BulkUserAction {
+    pub action: BulkAction,
+    pub emails: Vec<String>,
+    pub reason: String,
+    /// Unset until the admin has seen the confirmation page
+    pub confirmed: bool,
+}
+
+impl BulkUserAction {
+    pub fn from_pairs(pairs: Vec<(String, String)>) -> Result<Self, AppError> {
+        let mut action = None;
+        let mut emails = Vec::new();
+        let mut reason = String::new();
+        let mut confirmed = false;
+
+        for (key, value) in pairs {
+            match key.as_str() {
+                "action" => action = Some(value.parse()?),
+                "email" if !value.trim().is_empty() && !emails.contains(&value) => {
+                    emails.push(value)
+                }
+                "reason" => reason = value.trim().to_string(),
+                "confirm" => confirmed = value == "yes",
+                _ => {}
+            }
+        }
+
+        if emails.is_empty() {
+            return Err(AppError::InvalidBulkAction);
+        }
+
+        Ok(BulkUserAction {
+            action: action.ok_or(AppError::InvalidBulkAction)?,
+            emails,
+            reason,
+            confirmed,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, derive_more::Display)]
@@ -42,27 +153,22 @@ pub struct Claims {
 #[async_trait]
 impl<S> FromRequestParts<S> for Claims
 where
+    Arc<Config>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         //extract a token claims from our Authorization header
-        let jwt_token = parts
-            .headers
-            .get("cookie")
-            .and_then(|value| Cookie::parse(value.to_str().unwrap_or_default()).ok())
-            .and_then(|cookie| {
-                if cookie.name() == "jwt" {
-                    Some(cookie.value().to_string())
-                } else {
-                    None
-                }
-            })
+        let jwt_token = request_cookies(&parts.headers)
+            .get("jwt")
+            .map(|cookie| cookie.value().to_string())
             .ok_or(AppError::InvalidToken)?;
 
-        let token_data = decode::<Claims>(&jwt_token, &KEYS.decoding, &Validation::default())
+        let keys = Arc::<Config>::from_ref(state).keys();
+        let token_data = decode::<Claims>(&jwt_token, &keys.decoding, &Validation::default())
             .map_err(|_| AppError::InvalidToken)?;
+        tracing::Span::current().record("user_id", token_data.claims.id);
 
         Ok(token_data.claims)
     }
@@ -73,27 +179,22 @@ pub struct OptionalClaims(pub Option<Claims>);
 #[async_trait]
 impl<S> FromRequestParts<S> for OptionalClaims
 where
+    Arc<Config>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = Infallible; // Use Infallible since we're not rejecting the request
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         // Try extracting the JWT token from the "jwt" cookie
-        let jwt_token = parts
-            .headers
-            .get("cookie")
-            .and_then(|value| Cookie::parse(value.to_str().unwrap_or_default()).ok())
-            .and_then(|cookie| {
-                if cookie.name() == "jwt" {
-                    Some(cookie.value().to_string())
-                } else {
-                    None
-                }
-            });
+        let jwt_token = request_cookies(&parts.headers)
+            .get("jwt")
+            .map(|cookie| cookie.value().to_string());
 
         // If we have a JWT token, try to decode it
         if let Some(jwt) = jwt_token {
-            if let Ok(token_data) = decode::<Claims>(&jwt, &KEYS.decoding, &Validation::default()) {
+            let keys = Arc::<Config>::from_ref(state).keys();
+            if let Ok(token_data) = decode::<Claims>(&jwt, &keys.decoding, &Validation::default()) {
+                tracing::Span::current().record("user_id", token_data.claims.id);
                 return Ok(OptionalClaims(Some(token_data.claims)));
             }
         }
@@ -102,6 +203,23 @@ where
     }
 }
 
+/// Every cookie the browser sent. It sends them all in one `Cookie` header, so anything reading just the
+/// first `name=value` pair misses the rest. Values are percent-decoded, see `Flash::cookie`.
+pub fn request_cookies(headers: &HeaderMap) -> CookieJar {
+    let mut jar = CookieJar::new();
+    for header in headers.get_all(COOKIE) {
+        let Ok(header) = header.to_str() else {
+            continue;
+        };
+        for pair in header.split(';') {
+            if let Ok(cookie) = Cookie::parse_encoded(pair.trim().to_string()) {
+                jar.add_original(cookie);
+            }
+        }
+    }
+    jar
+}
+
 pub struct Keys {
     pub encoding: EncodingKey,
     pub decoding: DecodingKey,
@@ -116,8 +234,64 @@ impl Keys {
     }
 }
 
-pub static KEYS: Lazy<Keys> = Lazy::new(|| {
-    let secret = std::env::var("JWT_SECRET").expect("MISSING JWT SECRET!");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn from_pairs_collects_every_ticked_user_once() {
+        let bulk = BulkUserAction::from_pairs(pairs(&[
+            ("action", "ban"),
+            ("email", "ada@example.com"),
+            ("email", " "),
+            ("email", "bob@example.com"),
+            ("email", "ada@example.com"),
+            ("reason", "  Spam  "),
+            ("search", "example"),
+        ]))
+        .unwrap();
+        assert_eq!(bulk.action, BulkAction::Ban);
+        assert_eq!(bulk.emails, ["ada@example.com", "bob@example.com"]);
+        assert_eq!(bulk.reason, "Spam");
+        assert!(!bulk.confirmed);
+
+        let bulk = BulkUserAction::from_pairs(pairs(&[
+            ("action", "delete"),
+            ("email", "ada@example.com"),
+            ("confirm", "yes"),
+        ]))
+        .unwrap();
+        assert!(bulk.confirmed);
+    }
 
-    Keys::new(secret.as_bytes())
-});
+    #[test]
+    fn from_pairs_needs_an_action_and_a_user() {
+        for form in [
+            pairs(&[("email", "ada@example.com")]),
+            pairs(&[("action", "ban")]),
+            pairs(&[("action", "explode"), ("email", "ada@example.com")]),
+        ] {
+            assert!(matches!(
+                BulkUserAction::from_pairs(form),
+                Err(AppError::InvalidBulkAction)
+            ));
+        }
+    }
+
+    #[test]
+    fn bulk_actions_need_manage_users_and_the_single_action_rights() {
+        // Moderators can ban one user at a time, but not manage users in bulk
+        assert!(!BulkAction::Ban.is_allowed_for(Role::Moderator));
+        assert!(BulkAction::Ban.is_allowed_for(Role::Admin));
+        assert!(BulkAction::Delete.is_allowed_for(Role::Admin));
+        assert!(!BulkAction::Promote.is_allowed_for(Role::Admin));
+        assert!(BulkAction::Promote.is_allowed_for(Role::Owner));
+    }
+}
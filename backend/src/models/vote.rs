@@ -19,7 +19,21 @@ pub struct CreateVote {
     pub user_id: i32,
 }
 
+/// The JSON vote routes take the voter from the login cookie, so only the post comes from the body
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoteOnPost {
+    pub post_id: PostId,
+}
+
 #[derive(Deserialize)]
 pub struct GetVoteById {
     pub vote_id: i32,
 }
+
+/// What the JSON vote routes send back so the page can update without a redirect
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoteStatus {
+    pub post_id: PostId,
+    pub num_likes: i64,
+    pub already_liked: bool,
+}
@@ -0,0 +1,83 @@
+use axum::extract::State;
+use axum::response::{Html, Response};
+use axum::{Form, Json};
+use hyper::Body;
+use tera::Context;
+use tracing::error;
+
+use crate::db::Store;
+use crate::error::AppError;
+use crate::flash::IncomingFlash;
+use crate::handlers::create_redirect_to;
+use crate::models::notification::{MarkNotificationRead, NotificationSummary};
+use crate::models::user::Claims;
+use crate::template::TEMPLATES;
+
+// Notifications -------------------------------------------------------------------------------------------------------
+pub async fn notifications_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+    flash: IncomingFlash,
+) -> Result<(IncomingFlash, Html<String>), AppError> {
+    let current_user_id = am_database
+        .get_user_id_by_email(claims.email.clone())
+        .await?;
+    let notifications = am_database
+        .get_notifications_for_user(current_user_id)
+        .await?;
+    let unread_notifications = am_database
+        .count_unread_notifications(current_user_id)
+        .await?;
+
+    let mut context = Context::new();
+    context.insert("flash", &flash.0);
+    context.insert("claims", &claims);
+    context.insert("notifications", &notifications);
+    context.insert("unread_notifications", &unread_notifications);
+
+    let rendered = TEMPLATES
+        .render("notifications.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok((flash, Html(rendered)))
+}
+
+/// Polled by every open page, so it only counts, the notifications themselves are on the page
+pub async fn get_unread_count(
+    State(am_database): State<Store>,
+    claims: Claims,
+) -> Result<Json<NotificationSummary>, AppError> {
+    let current_user_id = am_database.get_user_id_by_email(claims.email).await?;
+    let unread_count = am_database
+        .count_unread_notifications(current_user_id)
+        .await?;
+
+    Ok(Json(NotificationSummary { unread_count }))
+}
+
+pub async fn mark_notification_read(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Form(notification): Form<MarkNotificationRead>,
+) -> Result<Response<Body>, AppError> {
+    let current_user_id = am_database.get_user_id_by_email(claims.email).await?;
+    am_database
+        .mark_notification_read(current_user_id, notification.id)
+        .await?;
+    let response = create_redirect_to("/notifications")?;
+    Ok(response)
+}
+
+pub async fn mark_all_notifications_read(
+    State(am_database): State<Store>,
+    claims: Claims,
+) -> Result<Response<Body>, AppError> {
+    let current_user_id = am_database.get_user_id_by_email(claims.email).await?;
+    am_database
+        .mark_all_notifications_read(current_user_id)
+        .await?;
+    let response = create_redirect_to("/notifications")?;
+    Ok(response)
+}
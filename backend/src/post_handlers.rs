@@ -1,19 +1,28 @@
-use crate::db::Store;
+use std::sync::Arc;
+
+use crate::db::{Store, RECOMMENDATION_LIMIT};
 use crate::error::AppError;
+use crate::handlers::require_permission;
 use crate::models::post::{CreatePost, Post, UpdatePost};
+use crate::models::revision::RevisionSource;
+use crate::models::role::Permission;
+use crate::models::similarity::SimilarPost;
+use crate::models::user::Claims;
+use crate::repository::Repository;
+use crate::revision_handlers::save_revision;
 use axum::extract::{Path, State};
 use axum::Json;
 
 // Posts ---------------------------------------------------------------------------------------------------------------
 pub async fn get_all_posts(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
 ) -> Result<Json<Vec<Post>>, AppError> {
     let posts = am_database.get_all_posts().await?;
     Ok(Json(posts))
 }
 
 pub async fn get_post_by_id(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
     Path(query): Path<i32>,
 ) -> Result<Json<Post>, AppError> {
     let post = am_database.get_post_by_id(query).await?;
@@ -21,35 +30,70 @@ pub async fn get_post_by_id(
 }
 
 pub async fn create_post(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
     Json(post): Json<CreatePost>,
 ) -> Result<Json<Post>, AppError> {
+    require_permission(am_database.as_ref(), &claims, Permission::EditPosts).await?;
     let new_post = am_database.add_post(post).await?;
     Ok(Json(new_post))
 }
 
 pub async fn delete_post_by_id(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
     Path(query): Path<i32>,
 ) -> Result<(), AppError> {
-    am_database.delete_post_by_id(query).await?;
+    require_permission(am_database.as_ref(), &claims, Permission::DeletePosts).await?;
+    am_database.delete_post_by_id(claims.email, query).await?;
 
     Ok(())
 }
 
 pub async fn update_post_by_id(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Store>,
+    claims: Claims,
     Json(updated_post): Json<UpdatePost>,
 ) -> Result<Json<Post>, AppError> {
-    let updated_post = am_database.update_post_by_id(updated_post).await?;
+    require_permission(&am_database, &claims, Permission::EditPosts).await?;
+    let old_post = am_database.get_post_by_id(updated_post.id.0).await?;
+    let updated_post = save_revision(
+        &am_database,
+        &claims,
+        old_post,
+        updated_post,
+        RevisionSource::Edit,
+    )
+    .await?;
 
     Ok(Json(updated_post))
 }
 
 pub async fn get_user_posts_by_id(
-    State(mut am_database): State<Store>,
+    State(am_database): State<Arc<dyn Repository>>,
     Path(query): Path<i32>,
 ) -> Result<Json<Vec<Post>>, AppError> {
     let user_posts = am_database.get_user_posts_by_id(query).await?;
     Ok(Json(user_posts))
 }
+
+pub async fn get_similar_posts(
+    State(am_database): State<Store>,
+    Path(query): Path<i32>,
+) -> Result<Json<Vec<SimilarPost>>, AppError> {
+    let similar_posts = am_database
+        .get_similar_posts(query, RECOMMENDATION_LIMIT)
+        .await?;
+    Ok(Json(similar_posts))
+}
+
+pub async fn get_recommended_posts(
+    State(am_database): State<Store>,
+    claims: Claims,
+) -> Result<Json<Vec<Post>>, AppError> {
+    let current_user_id = am_database.get_user_id_by_email(claims.email).await?;
+    let recommended_posts = am_database
+        .get_recommended_posts_for_user(current_user_id, RECOMMENDATION_LIMIT)
+        .await?;
+    Ok(Json(recommended_posts))
+}
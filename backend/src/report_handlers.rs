@@ -0,0 +1,160 @@
+use axum::extract::{Path, State};
+use axum::response::{Html, Response};
+use axum::Form;
+use hyper::Body;
+use tera::Context;
+use tracing::error;
+
+use crate::db::Store;
+use crate::error::AppError;
+use crate::flash::IncomingFlash;
+use crate::handlers::{
+    create_redirect_to, create_response_path, require_outranks, require_permission,
+};
+use crate::metrics::METRICS;
+use crate::models::report::{
+    ReportId, ReportPost, ReportResolution, ReportTarget, ReportUser, ResolveReport,
+};
+use crate::models::role::Permission;
+use crate::models::user::Claims;
+use crate::template::TEMPLATES;
+
+/// How many resolved reports to show under the queue
+const RECENTLY_RESOLVED_LIMIT: i64 = 20;
+
+// Reports -------------------------------------------------------------------------------------------------------------
+pub async fn report_post(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Form(report): Form<ReportPost>,
+) -> Result<Response<Body>, AppError> {
+    let reporter_id = am_database.get_user_id_by_email(claims.email).await?;
+    // Make sure the post exists before anyone has to moderate it
+    let post = am_database.get_post_by_id(report.post_id.0).await?;
+
+    am_database
+        .create_report(
+            reporter_id,
+            ReportTarget::Post,
+            post.id.0,
+            report_reason(&report.reason),
+        )
+        .await?;
+    let response = create_response_path()?;
+    Ok(response)
+}
+
+pub async fn report_user(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Form(report): Form<ReportUser>,
+) -> Result<Response<Body>, AppError> {
+    let reporter_id = am_database.get_user_id_by_email(claims.email).await?;
+    let target_id = am_database
+        .get_user_id_by_email(report.email)
+        .await
+        .map_err(|_| AppError::UserNotFound)?;
+    if target_id == reporter_id {
+        return Err(AppError::CannotActOnYourself);
+    }
+
+    am_database
+        .create_report(
+            reporter_id,
+            ReportTarget::User,
+            target_id,
+            report_reason(&report.reason),
+        )
+        .await?;
+    let response = create_response_path()?;
+    Ok(response)
+}
+
+// Moderation ----------------------------------------------------------------------------------------------------------
+pub async fn moderation_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+    flash: IncomingFlash,
+) -> Result<(IncomingFlash, Html<String>), AppError> {
+    let role = require_permission(&am_database, &claims, Permission::ModerateReports).await?;
+
+    let open_reports = am_database.get_open_reports().await?;
+    let resolved_reports = am_database
+        .get_resolved_reports(RECENTLY_RESOLVED_LIMIT)
+        .await?;
+    let permissions: Vec<String> = role
+        .permissions()
+        .iter()
+        .map(|permission| permission.to_string())
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("flash", &flash.0);
+    context.insert("open_reports", &open_reports);
+    context.insert("resolved_reports", &resolved_reports);
+    context.insert("permissions", &permissions);
+
+    let rendered = TEMPLATES
+        .render("moderation.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok((flash, Html(rendered)))
+}
+
+/// Closes a report, and every other open report about the same post or user, after carrying out
+/// the resolution. Deleting needs `DeletePosts` and banning needs `BanUsers` on top of moderating.
+pub async fn resolve_report(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Path(report_id): Path<i32>,
+    Form(resolve): Form<ResolveReport>,
+) -> Result<Response<Body>, AppError> {
+    let actor_role = require_permission(&am_database, &claims, Permission::ModerateReports).await?;
+
+    let report = am_database.get_report_by_id(ReportId(report_id)).await?;
+    match resolve.resolution {
+        ReportResolution::Dismissed => {}
+        ReportResolution::ContentDeleted => {
+            if !actor_role.has_permission(Permission::DeletePosts) {
+                return Err(AppError::MissingPermission);
+            }
+        }
+        ReportResolution::AuthorBanned => {
+            if !actor_role.has_permission(Permission::BanUsers) {
+                return Err(AppError::MissingPermission);
+            }
+            if report.target_kind == ReportTarget::User.to_string() {
+                let email = am_database
+                    .get_user_email_by_id(report.target_id)
+                    .await
+                    .map_err(|_| AppError::UserNotFound)?;
+                require_outranks(&am_database, &claims, actor_role, &email).await?;
+            }
+        }
+    }
+
+    am_database
+        .resolve_report(
+            claims.email,
+            actor_role,
+            &report,
+            resolve.resolution,
+            resolve.note.trim().to_string(),
+        )
+        .await?;
+    if resolve.resolution == ReportResolution::AuthorBanned {
+        METRICS.record_bans(1);
+    }
+
+    create_redirect_to("/moderation")
+}
+
+fn report_reason(reason: &str) -> String {
+    if reason.trim().is_empty() {
+        "No reason given".to_string()
+    } else {
+        reason.trim().to_string()
+    }
+}
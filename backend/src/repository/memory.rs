@@ -0,0 +1,565 @@
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::models::audit::{AuditAction, AuditEntry, AuditEntryId};
+use crate::models::ban::{Ban, BanId};
+use crate::models::nasaquery::NasaQuery;
+use crate::models::post::{CreatePost, Post, PostId};
+use crate::models::role::Role;
+use crate::models::user::{BulkAction, BulkUserAction, User, UserSignup};
+use crate::models::vote::{CreateVote, Vote, VoteId};
+use crate::repository::Repository;
+
+/// Keeps everything in memory, for tests and for trying handlers out without a database. Errors
+/// match what the Postgres implementation returns for the same mistake wherever they can.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    data: Mutex<Data>,
+}
+
+#[derive(Clone, Default)]
+struct Data {
+    users: Vec<MemoryUser>,
+    posts: Vec<Post>,
+    votes: Vec<Vote>,
+    bans: Vec<Ban>,
+    audit_log: Vec<AuditEntry>,
+    next_id: i32,
+}
+
+#[derive(Clone)]
+struct MemoryUser {
+    id: i32,
+    email: String,
+    password: String,
+    role: Role,
+    last_login_on: Option<DateTime<Utc>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every audit entry so far, oldest first
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.data.lock().unwrap().audit_log.clone()
+    }
+}
+
+impl Data {
+    /// Ids are shared between every kind of row, they only have to be unique within one
+    fn next_id(&mut self) -> i32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn user(&self, email: &str) -> Option<&MemoryUser> {
+        self.users.iter().find(|user| user.email == email)
+    }
+
+    fn user_mut(&mut self, email: &str) -> Result<&mut MemoryUser, AppError> {
+        self.users
+            .iter_mut()
+            .find(|user| user.email == email)
+            .ok_or(AppError::UserNotFound)
+    }
+
+    fn active_ban(&self, user_id: i32) -> Option<Ban> {
+        let now = Utc::now();
+        self.bans
+            .iter()
+            .filter(|ban| ban.user_id == user_id && is_active(ban, now))
+            .max_by_key(|ban| ban.started_on)
+            .map(|ban| Ban {
+                is_active: true,
+                ..ban.clone()
+            })
+    }
+
+    fn lift_bans(&mut self, user_id: i32) {
+        let now = Utc::now();
+        for ban in self.bans.iter_mut() {
+            let in_force = ban.expires_on.is_none_or(|expires_on| expires_on > now);
+            if ban.user_id == user_id && ban.lifted_on.is_none() && in_force {
+                ban.lifted_on = Some(now);
+                ban.is_active = false;
+            }
+        }
+    }
+
+    /// Same rule as Postgres, somebody else who isn't banned has to be left with admin rights
+    fn ensure_not_last_admin(&self, user_id: i32) -> Result<(), AppError> {
+        let other_admins = self
+            .users
+            .iter()
+            .filter(|user| user.role.is_admin() && user.id != user_id)
+            .filter(|user| self.active_ban(user.id).is_none())
+            .count();
+        if other_admins == 0 {
+            Err(AppError::LastAdmin)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_role(&mut self, email: &str, role: Role) -> Result<(), AppError> {
+        self.user_mut(email)?.role = role;
+        Ok(())
+    }
+
+    /// One user's part of a bulk action, the caller undoes everything if any of them fails
+    fn bulk_update_user(
+        &mut self,
+        actor_email: &str,
+        actor_id: i32,
+        actor_role: Role,
+        bulk: &BulkUserAction,
+        email: &str,
+    ) -> Result<(), AppError> {
+        let user = self.user_mut(email)?;
+        let (user_id, role) = (user.id, user.role);
+        if user_id == actor_id {
+            return Err(AppError::CannotActOnYourself);
+        }
+        if role >= actor_role {
+            return Err(AppError::CannotActOnUser);
+        }
+
+        let (action, payload) = match bulk.action {
+            BulkAction::Ban => {
+                if role.is_admin() {
+                    self.ensure_not_last_admin(user_id)?;
+                }
+                self.lift_bans(user_id);
+                let ban = Ban {
+                    id: BanId(self.next_id()),
+                    user_id,
+                    issued_by: Some(actor_id),
+                    issued_by_email: Some(actor_email.to_string()),
+                    reason: bulk.reason.clone(),
+                    started_on: Utc::now(),
+                    expires_on: None,
+                    lifted_on: None,
+                    is_active: true,
+                };
+                self.bans.push(ban);
+                (
+                    AuditAction::BanUser,
+                    json!({ "reason": bulk.reason, "expires_on": null, "bulk": true }),
+                )
+            }
+            BulkAction::Unban => {
+                self.lift_bans(user_id);
+                (AuditAction::UnbanUser, json!({ "bulk": true }))
+            }
+            BulkAction::Promote => {
+                if role.is_admin() {
+                    return Err(AppError::AlreadyAdmin);
+                }
+                self.set_role(email, Role::Admin)?;
+                (AuditAction::PromoteAdmin, json!({ "bulk": true }))
+            }
+            BulkAction::Delete => {
+                if role.is_admin() {
+                    self.ensure_not_last_admin(user_id)?;
+                }
+                self.users.retain(|user| user.id != user_id);
+                self.votes.retain(|vote| vote.user_id != user_id);
+                self.bans.retain(|ban| ban.user_id != user_id);
+                (AuditAction::DeleteUser, json!({ "bulk": true }))
+            }
+        };
+
+        self.record_audit(actor_email.to_string(), action, email.to_string(), payload);
+        Ok(())
+    }
+
+    fn record_audit(
+        &mut self,
+        actor_email: String,
+        action: AuditAction,
+        target: String,
+        payload: Value,
+    ) {
+        let actor_id = self.user(&actor_email).map(|user| user.id);
+        let entry = AuditEntry {
+            id: AuditEntryId(self.next_id()),
+            actor_id,
+            actor_email,
+            action: action.to_string(),
+            target,
+            payload,
+            created_on: Utc::now(),
+        };
+        self.audit_log.push(entry);
+    }
+}
+
+fn is_active(ban: &Ban, now: DateTime<Utc>) -> bool {
+    ban.lifted_on.is_none() && ban.expires_on.is_none_or(|expires_on| expires_on > now)
+}
+
+/// What `Store` returns when `fetch_one` finds nothing
+fn row_not_found() -> AppError {
+    AppError::NotFound
+}
+
+/// What `Store` returns for a unique or foreign key violation, with a friendlier message
+fn constraint_violation(message: &str) -> AppError {
+    AppError::Conflict(message.to_string())
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    // Users -----------------------------------------------------------------------------------------------------------
+    async fn get_user(&self, email: &str) -> Result<User, AppError> {
+        let data = self.data.lock().unwrap();
+        let user = data.user(email).ok_or_else(row_not_found)?;
+        Ok(User {
+            email: user.email.clone(),
+            password: user.password.clone(),
+        })
+    }
+
+    async fn get_user_id_by_email(&self, email: String) -> Result<i32, AppError> {
+        let data = self.data.lock().unwrap();
+        data.user(&email)
+            .map(|user| user.id)
+            .ok_or_else(row_not_found)
+    }
+
+    async fn create_user(&self, user: UserSignup) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        if data.user(&user.email).is_some() {
+            return Err(constraint_violation(
+                "A user with that email already exists",
+            ));
+        }
+        let id = data.next_id();
+        data.users.push(MemoryUser {
+            id,
+            email: user.email,
+            password: user.password,
+            role: Role::User,
+            last_login_on: None,
+        });
+        Ok(())
+    }
+
+    async fn record_login(&self, email: String) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        if let Ok(user) = data.user_mut(&email) {
+            user.last_login_on = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    async fn get_user_role_by_email(&self, email: String) -> Result<Role, AppError> {
+        let data = self.data.lock().unwrap();
+        data.user(&email)
+            .map(|user| user.role)
+            .ok_or(AppError::UserNotFound)
+    }
+
+    async fn determine_if_user_banned(&self, email: String) -> Result<bool, AppError> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .user(&email)
+            .is_some_and(|user| data.active_ban(user.id).is_some()))
+    }
+
+    // Posts -----------------------------------------------------------------------------------------------------------
+    async fn get_all_posts(&self) -> Result<Vec<Post>, AppError> {
+        Ok(self.data.lock().unwrap().posts.clone())
+    }
+
+    async fn get_post_by_id(&self, post_id: i32) -> Result<Post, AppError> {
+        let data = self.data.lock().unwrap();
+        data.posts
+            .iter()
+            .find(|post| post.id.0 == post_id)
+            .cloned()
+            .ok_or_else(row_not_found)
+    }
+
+    async fn get_post_by_query_string(&self, query: NasaQuery) -> Result<Post, AppError> {
+        let data = self.data.lock().unwrap();
+        data.posts
+            .iter()
+            .find(|post| post.query_string == query.query_string)
+            .cloned()
+            .ok_or_else(row_not_found)
+    }
+
+    async fn check_cache_by_query_string(&self, query: NasaQuery) -> Result<bool, AppError> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .posts
+            .iter()
+            .any(|post| post.query_string == query.query_string))
+    }
+
+    async fn add_post(&self, new_post: CreatePost) -> Result<Post, AppError> {
+        let mut data = self.data.lock().unwrap();
+        if data.posts.iter().any(|post| post.title == new_post.title) {
+            return Err(constraint_violation(
+                "A post with that title already exists",
+            ));
+        }
+        let post = Post {
+            id: PostId(data.next_id()),
+            title: new_post.title,
+            query_string: new_post.query_string,
+            explanation: new_post.explanation,
+            img_url: new_post.img_url,
+            apod_date: new_post.apod_date,
+        };
+        data.posts.push(post.clone());
+        Ok(post)
+    }
+
+    async fn delete_post_by_id(&self, actor_email: String, post_id: i32) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        let old_post = data
+            .posts
+            .iter()
+            .find(|post| post.id.0 == post_id)
+            .cloned()
+            .ok_or_else(row_not_found)?;
+        data.posts.retain(|post| post.id.0 != post_id);
+        data.votes.retain(|vote| vote.post_id.0 != post_id);
+        data.record_audit(
+            actor_email,
+            AuditAction::DeletePost,
+            format!("post {}", post_id),
+            json!({ "post": old_post }),
+        );
+        Ok(())
+    }
+
+    async fn get_user_posts_by_id(&self, user_id: i32) -> Result<Vec<Post>, AppError> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .votes
+            .iter()
+            .filter(|vote| vote.user_id == user_id)
+            .filter_map(|vote| data.posts.iter().find(|post| post.id == vote.post_id))
+            .cloned()
+            .collect())
+    }
+
+    // Votes -----------------------------------------------------------------------------------------------------------
+    async fn create_vote(&self, new_vote: CreateVote) -> Result<Vote, AppError> {
+        let mut data = self.data.lock().unwrap();
+        if !data.posts.iter().any(|post| post.id == new_vote.post_id)
+            || !data.users.iter().any(|user| user.id == new_vote.user_id)
+        {
+            return Err(constraint_violation("That post or user doesn't exist"));
+        }
+        if data
+            .votes
+            .iter()
+            .any(|vote| vote.post_id == new_vote.post_id && vote.user_id == new_vote.user_id)
+        {
+            return Err(constraint_violation("That user already liked that post"));
+        }
+        let vote = Vote {
+            id: VoteId(data.next_id()),
+            post_id: new_vote.post_id,
+            user_id: new_vote.user_id,
+        };
+        data.votes.push(vote.clone());
+        Ok(vote)
+    }
+
+    async fn delete_vote(&self, old_vote: CreateVote) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        data.votes
+            .retain(|vote| !(vote.post_id == old_vote.post_id && vote.user_id == old_vote.user_id));
+        Ok(())
+    }
+
+    async fn get_number_of_votes_for_post(&self, post_id: i32) -> Result<i64, AppError> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .votes
+            .iter()
+            .filter(|vote| vote.post_id.0 == post_id)
+            .count() as i64)
+    }
+
+    async fn determine_if_user_liked_post(
+        &self,
+        user_id: i32,
+        post_id: i32,
+    ) -> Result<bool, AppError> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .votes
+            .iter()
+            .any(|vote| vote.user_id == user_id && vote.post_id.0 == post_id))
+    }
+
+    // Admin -----------------------------------------------------------------------------------------------------------
+    async fn promote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        let role = data.user_mut(&email)?.role;
+        if !role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if role.is_admin() {
+            return Err(AppError::AlreadyAdmin);
+        }
+        data.set_role(&email, Role::Admin)?;
+        data.record_audit(actor_email, AuditAction::PromoteAdmin, email, json!({}));
+        Ok(())
+    }
+
+    async fn demote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        let user = data.user_mut(&email)?;
+        if !user.role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if !user.role.is_admin() {
+            return Err(AppError::NotAnAdmin);
+        }
+        let user_id = user.id;
+        data.ensure_not_last_admin(user_id)?;
+        data.set_role(&email, Role::User)?;
+        data.record_audit(actor_email, AuditAction::DemoteAdmin, email, json!({}));
+        Ok(())
+    }
+
+    async fn set_user_role_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        role: Role,
+    ) -> Result<Role, AppError> {
+        let mut data = self.data.lock().unwrap();
+        let user = data.user_mut(&email)?;
+        let (user_id, old_role) = (user.id, user.role);
+        if !old_role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if old_role.is_admin() && !role.is_admin() {
+            data.ensure_not_last_admin(user_id)?;
+        }
+        data.set_role(&email, role)?;
+        data.record_audit(
+            actor_email,
+            AuditAction::SetRole,
+            email,
+            json!({ "from": old_role, "to": role }),
+        );
+        Ok(old_role)
+    }
+
+    async fn ban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        reason: String,
+        expires_on: Option<DateTime<Utc>>,
+    ) -> Result<Ban, AppError> {
+        let mut data = self.data.lock().unwrap();
+        let issued_by = data.user(&actor_email).map(|user| user.id);
+        let user = data.user_mut(&email)?;
+        let (user_id, role) = (user.id, user.role);
+        if issued_by == Some(user_id) {
+            return Err(AppError::CannotActOnYourself);
+        }
+        if !role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        if role.is_admin() {
+            data.ensure_not_last_admin(user_id)?;
+        }
+
+        data.lift_bans(user_id);
+        let ban = Ban {
+            id: BanId(data.next_id()),
+            user_id,
+            issued_by,
+            issued_by_email: issued_by.map(|_| actor_email.clone()),
+            reason: reason.clone(),
+            started_on: Utc::now(),
+            expires_on,
+            lifted_on: None,
+            is_active: expires_on.is_none_or(|expires_on| expires_on > Utc::now()),
+        };
+        data.bans.push(ban);
+        data.record_audit(
+            actor_email,
+            AuditAction::BanUser,
+            email,
+            json!({ "reason": reason, "expires_on": expires_on }),
+        );
+
+        data.active_ban(user_id)
+            .ok_or(AppError::InternalServerError)
+    }
+
+    async fn unban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        let user = data.user_mut(&email)?;
+        if !user.role.is_outranked_by(actor_role) {
+            return Err(AppError::CannotActOnUser);
+        }
+        let user_id = user.id;
+        data.lift_bans(user_id);
+        data.record_audit(actor_email, AuditAction::UnbanUser, email, json!({}));
+        Ok(())
+    }
+
+    async fn get_active_ban_by_email(&self, email: String) -> Result<Option<Ban>, AppError> {
+        let data = self.data.lock().unwrap();
+        Ok(data.user(&email).and_then(|user| data.active_ban(user.id)))
+    }
+
+    async fn bulk_update_users(
+        &self,
+        actor_email: String,
+        bulk: &BulkUserAction,
+    ) -> Result<(), AppError> {
+        let mut data = self.data.lock().unwrap();
+        let actor = data.user(&actor_email).ok_or(AppError::UserNotFound)?;
+        let (actor_id, actor_role) = (actor.id, actor.role);
+        if !bulk.action.is_allowed_for(actor_role) {
+            return Err(AppError::MissingPermission);
+        }
+
+        let before = data.clone();
+        for email in &bulk.emails {
+            if let Err(err) = data.bulk_update_user(&actor_email, actor_id, actor_role, bulk, email)
+            {
+                *data = before;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! Persistence behind a trait, so handlers that only need users, posts, votes and admin actions can
+//! run against Postgres or an in-memory store. The routes on it are registration and login, the
+//! `/posts` JSON API apart from edits and recommendations, votes, and the single and bulk admin
+//! actions. Everything else (the front page, APOD fetching, feeds, follows, notifications, reports,
+//! revisions, settings, the dashboard, ban history, the audit log, the probes and live events) still
+//! takes `Store` and needs Postgres.
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+use crate::models::ban::Ban;
+use crate::models::nasaquery::NasaQuery;
+use crate::models::post::{CreatePost, Post};
+use crate::models::role::Role;
+use crate::models::user::{BulkUserAction, User, UserSignup};
+use crate::models::vote::{CreateVote, Vote};
+
+pub mod memory;
+mod postgres;
+
+pub use memory::InMemoryRepository;
+
+#[async_trait]
+pub trait Repository: Send + Sync {
+    // Users -----------------------------------------------------------------------------------------------------------
+    async fn get_user(&self, email: &str) -> Result<User, AppError>;
+    async fn get_user_id_by_email(&self, email: String) -> Result<i32, AppError>;
+    /// `user.password` has to be hashed already
+    async fn create_user(&self, user: UserSignup) -> Result<(), AppError>;
+    async fn record_login(&self, email: String) -> Result<(), AppError>;
+    async fn get_user_role_by_email(&self, email: String) -> Result<Role, AppError>;
+    async fn determine_if_user_banned(&self, email: String) -> Result<bool, AppError>;
+
+    // Posts -----------------------------------------------------------------------------------------------------------
+    async fn get_all_posts(&self) -> Result<Vec<Post>, AppError>;
+    async fn get_post_by_id(&self, post_id: i32) -> Result<Post, AppError>;
+    async fn get_post_by_query_string(&self, query: NasaQuery) -> Result<Post, AppError>;
+    async fn check_cache_by_query_string(&self, query: NasaQuery) -> Result<bool, AppError>;
+    async fn add_post(&self, new_post: CreatePost) -> Result<Post, AppError>;
+    /// Writes its own audit entry as `actor_email`
+    async fn delete_post_by_id(&self, actor_email: String, post_id: i32) -> Result<(), AppError>;
+    /// The posts `user_id` has liked
+    async fn get_user_posts_by_id(&self, user_id: i32) -> Result<Vec<Post>, AppError>;
+
+    // Votes -----------------------------------------------------------------------------------------------------------
+    async fn create_vote(&self, new_vote: CreateVote) -> Result<Vote, AppError>;
+    async fn delete_vote(&self, old_vote: CreateVote) -> Result<(), AppError>;
+    async fn get_number_of_votes_for_post(&self, post_id: i32) -> Result<i64, AppError>;
+    async fn determine_if_user_liked_post(
+        &self,
+        user_id: i32,
+        post_id: i32,
+    ) -> Result<bool, AppError>;
+
+    // Admin -----------------------------------------------------------------------------------------------------------
+    // Each of these writes its own audit entry as `actor_email`, together with the change itself, and
+    // refuses targets `actor_role` doesn't outrank once they're locked. `None` is the admin CLI.
+    async fn promote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError>;
+    /// Refuses to demote the last admin
+    async fn demote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError>;
+    /// Returns the role they had before
+    async fn set_user_role_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        role: Role,
+    ) -> Result<Role, AppError>;
+    /// Replaces any ban the user already had, `expires_on` of `None` lasts until it's lifted
+    async fn ban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        reason: String,
+        expires_on: Option<DateTime<Utc>>,
+    ) -> Result<Ban, AppError>;
+    async fn unban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError>;
+    async fn get_active_ban_by_email(&self, email: String) -> Result<Option<Ban>, AppError>;
+    /// All or nothing, `actor_email` has to be allowed the action and outrank every user in it
+    async fn bulk_update_users(
+        &self,
+        actor_email: String,
+        bulk: &BulkUserAction,
+    ) -> Result<(), AppError>;
+}
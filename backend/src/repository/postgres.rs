@@ -0,0 +1,152 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::db::Store;
+use crate::error::AppError;
+use crate::models::ban::Ban;
+use crate::models::nasaquery::NasaQuery;
+use crate::models::post::{CreatePost, Post};
+use crate::models::role::Role;
+use crate::models::user::{BulkUserAction, User, UserSignup};
+use crate::models::vote::{CreateVote, Vote};
+use crate::repository::Repository;
+
+/// Hands everything to the `Store` methods of the same name
+#[async_trait]
+impl Repository for Store {
+    // Users -----------------------------------------------------------------------------------------------------------
+    async fn get_user(&self, email: &str) -> Result<User, AppError> {
+        Store::get_user(self, email).await
+    }
+
+    async fn get_user_id_by_email(&self, email: String) -> Result<i32, AppError> {
+        Store::get_user_id_by_email(self, email).await
+    }
+
+    async fn create_user(&self, user: UserSignup) -> Result<(), AppError> {
+        Store::create_user(self, user).await.map(|_| ())
+    }
+
+    async fn record_login(&self, email: String) -> Result<(), AppError> {
+        Store::record_login(self, email).await
+    }
+
+    async fn get_user_role_by_email(&self, email: String) -> Result<Role, AppError> {
+        Store::get_user_role_by_email(self, email).await
+    }
+
+    async fn determine_if_user_banned(&self, email: String) -> Result<bool, AppError> {
+        Store::determine_if_user_banned(self, email).await
+    }
+
+    // Posts -----------------------------------------------------------------------------------------------------------
+    async fn get_all_posts(&self) -> Result<Vec<Post>, AppError> {
+        Store::get_all_posts(self).await
+    }
+
+    async fn get_post_by_id(&self, post_id: i32) -> Result<Post, AppError> {
+        Store::get_post_by_id(self, post_id).await
+    }
+
+    async fn get_post_by_query_string(&self, query: NasaQuery) -> Result<Post, AppError> {
+        Store::get_post_by_query_string(self, query).await
+    }
+
+    async fn check_cache_by_query_string(&self, query: NasaQuery) -> Result<bool, AppError> {
+        Store::check_cache_by_query_string(self, query).await
+    }
+
+    async fn add_post(&self, new_post: CreatePost) -> Result<Post, AppError> {
+        Store::add_post(self, new_post).await
+    }
+
+    async fn delete_post_by_id(&self, actor_email: String, post_id: i32) -> Result<(), AppError> {
+        Store::delete_post_by_id(self, actor_email, post_id).await
+    }
+
+    async fn get_user_posts_by_id(&self, user_id: i32) -> Result<Vec<Post>, AppError> {
+        Store::get_user_posts_by_id(self, user_id).await
+    }
+
+    // Votes -----------------------------------------------------------------------------------------------------------
+    async fn create_vote(&self, new_vote: CreateVote) -> Result<Vote, AppError> {
+        Store::create_vote(self, new_vote).await
+    }
+
+    async fn delete_vote(&self, old_vote: CreateVote) -> Result<(), AppError> {
+        Store::delete_vote(self, old_vote).await
+    }
+
+    async fn get_number_of_votes_for_post(&self, post_id: i32) -> Result<i64, AppError> {
+        Store::get_number_of_votes_for_post(self, post_id).await
+    }
+
+    async fn determine_if_user_liked_post(
+        &self,
+        user_id: i32,
+        post_id: i32,
+    ) -> Result<bool, AppError> {
+        Store::determine_if_user_liked_post(self, user_id, post_id).await
+    }
+
+    // Admin -----------------------------------------------------------------------------------------------------------
+    async fn promote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError> {
+        Store::promote_admin_by_email(self, actor_email, actor_role, email).await
+    }
+
+    async fn demote_admin_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError> {
+        Store::demote_admin_by_email(self, actor_email, actor_role, email).await
+    }
+
+    async fn set_user_role_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        role: Role,
+    ) -> Result<Role, AppError> {
+        Store::set_user_role_by_email(self, actor_email, actor_role, email, role).await
+    }
+
+    async fn ban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+        reason: String,
+        expires_on: Option<DateTime<Utc>>,
+    ) -> Result<Ban, AppError> {
+        Store::ban_user_by_email(self, actor_email, actor_role, email, reason, expires_on).await
+    }
+
+    async fn unban_user_by_email(
+        &self,
+        actor_email: String,
+        actor_role: Option<Role>,
+        email: String,
+    ) -> Result<(), AppError> {
+        Store::unban_user_by_email(self, actor_email, actor_role, email).await
+    }
+
+    async fn get_active_ban_by_email(&self, email: String) -> Result<Option<Ban>, AppError> {
+        Store::get_active_ban_by_email(self, email).await
+    }
+
+    async fn bulk_update_users(
+        &self,
+        actor_email: String,
+        bulk: &BulkUserAction,
+    ) -> Result<(), AppError> {
+        Store::bulk_update_users(self, actor_email, bulk).await
+    }
+}
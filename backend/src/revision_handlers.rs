@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::response::{Html, Response};
+use axum::Form;
+use hyper::Body;
+use tera::Context;
+use tracing::error;
+
+use crate::config::Config;
+use crate::db::Store;
+use crate::diff::diff_field;
+use crate::error::{AppError, FieldError};
+use crate::flash::IncomingFlash;
+use crate::handlers::{create_redirect_to, fetch_apod, require_permission};
+use crate::models::nasaquery::NasaQuery;
+use crate::models::post::{Post, UpdatePost};
+use crate::models::revision::{EditPost, PostRevisionId, RevisionDiffQuery, RevisionSource};
+use crate::models::role::Permission;
+use crate::models::user::Claims;
+use crate::template::TEMPLATES;
+
+// Revisions -----------------------------------------------------------------------------------------------------------
+pub async fn edit_post_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Path(post_id): Path<i32>,
+    flash: IncomingFlash,
+) -> Result<(IncomingFlash, Html<String>), AppError> {
+    require_permission(&am_database, &claims, Permission::EditPosts).await?;
+
+    let post = am_database.get_post_by_id(post_id).await?;
+    let revisions = am_database.get_post_revisions(post_id).await?;
+
+    let mut context = Context::new();
+    context.insert("flash", &flash.0);
+    context.insert("post", &post);
+    context.insert("revisions", &revisions);
+
+    let rendered = TEMPLATES
+        .render("edit_post.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok((flash, Html(rendered)))
+}
+
+pub async fn edit_post(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Path(post_id): Path<i32>,
+    Form(edit): Form<EditPost>,
+) -> Result<Response<Body>, AppError> {
+    require_permission(&am_database, &claims, Permission::EditPosts).await?;
+
+    let old_post = am_database.get_post_by_id(post_id).await?;
+    let update = UpdatePost {
+        id: old_post.id,
+        title: edit.title.trim().to_string(),
+        query_string: old_post.query_string.clone(),
+        explanation: edit.explanation.trim().to_string(),
+        img_url: edit.img_url.trim().to_string(),
+        apod_date: edit.apod_date.trim().to_string(),
+    };
+    save_revision(
+        &am_database,
+        &claims,
+        old_post,
+        update,
+        RevisionSource::Edit,
+    )
+    .await?;
+
+    create_redirect_to(&format!("/posts/{}/edit", post_id))
+}
+
+pub async fn revision_diff(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Path(post_id): Path<i32>,
+    Query(query): Query<RevisionDiffQuery>,
+) -> Result<Html<String>, AppError> {
+    require_permission(&am_database, &claims, Permission::EditPosts).await?;
+
+    let post = am_database.get_post_by_id(post_id).await?;
+    let from = am_database.get_post_revision(post_id, query.from).await?;
+    let to = am_database.get_post_revision(post_id, query.to).await?;
+    let diffs = vec![
+        diff_field("title", &from.title, &to.title),
+        diff_field("explanation", &from.explanation, &to.explanation),
+        diff_field("img_url", &from.img_url, &to.img_url),
+        diff_field("apod_date", &from.apod_date, &to.apod_date),
+    ];
+
+    let mut context = Context::new();
+    context.insert("post", &post);
+    context.insert("from", &from);
+    context.insert("to", &to);
+    context.insert("diffs", &diffs);
+
+    let rendered = TEMPLATES
+        .render("revision_diff.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok(Html(rendered))
+}
+
+/// Puts an old revision back. That's saved as a new revision, so rolling back can be undone too.
+pub async fn rollback_post(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Path((post_id, revision_id)): Path<(i32, i32)>,
+) -> Result<Response<Body>, AppError> {
+    require_permission(&am_database, &claims, Permission::EditPosts).await?;
+
+    let old_post = am_database.get_post_by_id(post_id).await?;
+    let revision = am_database
+        .get_post_revision(post_id, PostRevisionId(revision_id))
+        .await?;
+    let update = UpdatePost {
+        id: old_post.id,
+        title: revision.title,
+        query_string: old_post.query_string.clone(),
+        explanation: revision.explanation,
+        img_url: revision.img_url,
+        apod_date: revision.apod_date,
+    };
+    save_revision(
+        &am_database,
+        &claims,
+        old_post,
+        update,
+        RevisionSource::Rollback,
+    )
+    .await?;
+
+    create_redirect_to(&format!("/posts/{}/edit", post_id))
+}
+
+/// Fetches the post's APOD again, for when it was mis-ingested or NASA has since fixed it
+pub async fn resync_post(
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    Path(post_id): Path<i32>,
+) -> Result<Response<Body>, AppError> {
+    require_permission(&am_database, &claims, Permission::EditPosts).await?;
+
+    let old_post = am_database.get_post_by_id(post_id).await?;
+    let query = NasaQuery {
+        query_string: old_post.query_string.clone(),
+    };
+    let fetched = fetch_apod(&config, &query).await;
+    am_database
+        .record_nasa_fetch(
+            query.query_string,
+            fetched.as_ref().err().map(|err| format!("{:?}", err)),
+        )
+        .await?;
+    let fetched = fetched?;
+
+    let update = UpdatePost {
+        id: old_post.id,
+        title: fetched.title,
+        query_string: fetched.query_string,
+        explanation: fetched.explanation,
+        img_url: fetched.img_url,
+        apod_date: fetched.apod_date,
+    };
+    save_revision(
+        &am_database,
+        &claims,
+        old_post,
+        update,
+        RevisionSource::NasaSync,
+    )
+    .await?;
+
+    create_redirect_to(&format!("/posts/{}/edit", post_id))
+}
+
+/// Checks the new version of a post, then saves it as a revision and writes the change to the
+/// audit log
+pub async fn save_revision(
+    am_database: &Store,
+    claims: &Claims,
+    old_post: Post,
+    update: UpdatePost,
+    source: RevisionSource,
+) -> Result<Post, AppError> {
+    let mut problems = Vec::new();
+    if update.title.is_empty() {
+        problems.push(FieldError::new("title", "Enter a title"));
+    }
+    if update.apod_date.is_empty() {
+        problems.push(FieldError::new("apod_date", "Enter the APOD date"));
+    }
+    if !problems.is_empty() {
+        return Err(AppError::Validation(problems));
+    }
+
+    am_database
+        .update_post_with_revision(claims.email.clone(), old_post, update, source)
+        .await
+}
@@ -1,41 +1,133 @@
-use axum::response::Response;
+use std::sync::Arc;
+
+use axum::middleware;
 use axum::routing::*;
 use axum::Router;
-use http::StatusCode;
-use hyper::Body;
 use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
 
+use crate::config::Config;
 use crate::db::Store;
+use crate::error::{self, AppError};
 use crate::handlers::root;
 // use crate::routes::comment_routes::comment_routes;
 use crate::admin_handlers;
+use crate::follow_handlers;
+use crate::health_handlers;
+use crate::live_handlers;
+use crate::notification_handlers;
 use crate::post_handlers;
+use crate::report_handlers;
+use crate::revision_handlers;
+use crate::settings_handlers;
+use crate::state::AppState;
+use crate::syndication_handlers;
 use crate::user_handlers;
 use crate::vote_handlers;
 use crate::{handlers, layers};
 
-pub async fn app(pool: PgPool) -> Router {
+/// The full app on top of `pool`. Background tasks are started by `run_backend`, not here, so
+/// building an app (in tests, say) never leaves workers running. `shutdown` is the token they watch.
+pub fn app(pool: PgPool, config: Arc<Config>, shutdown: CancellationToken) -> Router {
     let db = Store::with_pool(pool);
 
-    let (cors_layer, trace_layer) = layers::get_layers();
+    router(AppState {
+        repo: Arc::new(db.clone()),
+        store: db,
+        config,
+        shutdown,
+    })
+}
+
+/// Every route against the given state, without starting any background tasks. `state.repo` can be
+/// an `InMemoryRepository`, the routes listed in `repository` then never touch Postgres.
+pub fn router(state: AppState) -> Router {
+    let (cors_layer, trace_layer, metrics_layer, set_request_id_layer, propagate_request_id_layer) =
+        layers::get_layers();
 
     Router::new()
         .route("/", get(root))
+        // Probes
+        .route("/healthz", get(health_handlers::healthz))
+        .route("/readyz", get(health_handlers::readyz))
+        .route("/version", get(health_handlers::version))
+        .route("/metrics", get(health_handlers::metrics))
         // User login / registration
         .route("/users", post(user_handlers::register))
         .route("/login", post(user_handlers::login))
         .route("/logout", get(user_handlers::logout))
         .route("/protected", get(handlers::protected))
+        // Settings
+        .route("/settings", get(settings_handlers::settings_page))
+        .route("/settings/export", get(settings_handlers::export_account))
+        .route("/settings/delete", post(settings_handlers::delete_account))
         // Posts
         .route("/posts", get(post_handlers::get_all_posts))
         .route("/posts/:id", get(post_handlers::get_post_by_id))
         .route("/posts", post(post_handlers::create_post))
         .route("/posts/:id", delete(post_handlers::delete_post_by_id))
         .route("/posts", put(post_handlers::update_post_by_id))
+        .route(
+            "/posts/:id/edit",
+            get(revision_handlers::edit_post_page).post(revision_handlers::edit_post),
+        )
+        .route(
+            "/posts/:id/revisions/diff",
+            get(revision_handlers::revision_diff),
+        )
+        .route(
+            "/posts/:id/revisions/:revision_id/rollback",
+            post(revision_handlers::rollback_post),
+        )
+        .route("/posts/:id/resync", post(revision_handlers::resync_post))
         .route("/users/:id/posts", get(post_handlers::get_user_posts_by_id))
+        .route("/posts/:id/similar", get(post_handlers::get_similar_posts))
+        .route(
+            "/recommendations",
+            get(post_handlers::get_recommended_posts),
+        )
         // Votes
         .route("/votes", post(vote_handlers::create_vote_from_form))
         .route("/votes/delete", post(vote_handlers::delete_vote_from_form))
+        .route("/votes/json", post(vote_handlers::create_vote_json))
+        .route("/votes/json/delete", post(vote_handlers::delete_vote_json))
+        // Feeds
+        .route("/feed.rss", get(syndication_handlers::all_posts_rss))
+        .route("/feed.atom", get(syndication_handlers::all_posts_atom))
+        .route(
+            "/users/:id/feed.rss",
+            get(syndication_handlers::user_likes_rss),
+        )
+        .route(
+            "/users/:id/feed.atom",
+            get(syndication_handlers::user_likes_atom),
+        )
+        // Live updates
+        .route("/events", get(live_handlers::live_events))
+        // Follows
+        .route("/follow", post(follow_handlers::follow_user))
+        .route("/unfollow", post(follow_handlers::unfollow_user))
+        .route("/feed", get(follow_handlers::get_feed))
+        // Notifications
+        .route(
+            "/notifications",
+            get(notification_handlers::notifications_page),
+        )
+        .route(
+            "/notifications/unread",
+            get(notification_handlers::get_unread_count),
+        )
+        .route(
+            "/notifications/read",
+            post(notification_handlers::mark_notification_read),
+        )
+        .route(
+            "/notifications/read_all",
+            post(notification_handlers::mark_all_notifications_read),
+        )
+        // Reports
+        .route("/reports/post", post(report_handlers::report_post))
+        .route("/reports/user", post(report_handlers::report_user))
         // NASA
         // .route("/get_apod", post(handlers::get_nasa_post))
         .route("/get_apod", post(handlers::get_nasa_post_by_form))
@@ -44,17 +136,34 @@ pub async fn app(pool: PgPool) -> Router {
         // Admin
         .route("/ban", post(admin_handlers::ban_user))
         .route("/unban", post(admin_handlers::unban_user))
+        .route("/bans", get(admin_handlers::ban_history))
+        .route("/audit", get(admin_handlers::audit_log_page))
+        .route("/audit/export", get(admin_handlers::export_audit_log))
         .route("/promote", post(admin_handlers::promote_admin))
         .route("/demote", post(admin_handlers::demote_admin))
+        .route("/role", post(admin_handlers::set_role))
+        .route("/users", get(admin_handlers::user_management_page))
+        .route("/users/bulk", post(admin_handlers::bulk_user_action))
+        .route("/dashboard", get(admin_handlers::dashboard_page))
+        .route("/dashboard/json", get(admin_handlers::dashboard_json))
+        .route("/moderation", get(report_handlers::moderation_page))
+        .route(
+            "/moderation/:id/resolve",
+            post(report_handlers::resolve_report),
+        )
         // .merge(comment_routes())
+        .layer(metrics_layer)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            error::negotiate_errors,
+        ))
         .layer(cors_layer)
         .layer(trace_layer)
-        .with_state(db)
+        .layer(propagate_request_id_layer)
+        .layer(set_request_id_layer)
+        .with_state(state)
 }
 
-async fn handle_404() -> Response<Body> {
-    Response::builder()
-        .status(StatusCode::NOT_FOUND)
-        .body(Body::from("The requested page could not be found"))
-        .unwrap()
+async fn handle_404() -> AppError {
+    AppError::NotFound
 }
@@ -0,0 +1,82 @@
+use crate::config::Config;
+use crate::db::Store;
+use crate::error::AppError;
+use crate::models::nasaquery::NasaQuery;
+use crate::models::seed::{Fixture, SeedSummary};
+use crate::models::user::UserSignup;
+use crate::models::vote::CreateVote;
+use crate::user_handlers::hash_password;
+
+/// Who the audit log says gave the demo users their roles
+const SEED_ACTOR: &str = "seed";
+
+/// Bundled into the binary so seeding works from anywhere, without the NASA API
+const DEMO_FIXTURE: &str = include_str!("../tests/fixtures/demo.json");
+
+pub fn demo_fixture() -> Result<Fixture, AppError> {
+    serde_json::from_str(DEMO_FIXTURE).map_err(|err| AppError::Any(err.into()))
+}
+
+/// Loads the fixture's users, posts and votes. Anything that already exists is skipped, so running
+/// it twice is harmless. Votes go through `create_vote` so the feeds and live counts see them too.
+pub async fn seed(
+    store: &Store,
+    config: &Config,
+    fixture: Fixture,
+) -> Result<SeedSummary, AppError> {
+    let mut summary = SeedSummary::default();
+
+    for user in fixture.users {
+        if store.get_user(&user.email).await.is_ok() {
+            continue;
+        }
+        let _ = store
+            .create_user(UserSignup {
+                email: user.email.clone(),
+                password: hash_password(&user.password, &config.salt)?,
+                confirm_password: String::new(),
+            })
+            .await?;
+        if let Some(role) = user.role {
+            store
+                .set_user_role_by_email(SEED_ACTOR.to_string(), None, user.email, role)
+                .await?;
+        }
+        summary.users += 1;
+    }
+
+    for post in fixture.posts {
+        let query = NasaQuery {
+            query_string: post.query_string.clone(),
+        };
+        if store.check_cache_by_query_string(query).await? {
+            continue;
+        }
+        store.add_post(post).await?;
+        summary.posts += 1;
+    }
+
+    for vote in fixture.votes {
+        let user_id = store.get_user_id_by_email(vote.email).await?;
+        let post = store
+            .get_post_by_query_string(NasaQuery {
+                query_string: vote.query_string,
+            })
+            .await?;
+        if store
+            .determine_if_user_liked_post(user_id, post.id.0)
+            .await?
+        {
+            continue;
+        }
+        store
+            .create_vote(CreateVote {
+                post_id: post.id,
+                user_id,
+            })
+            .await?;
+        summary.votes += 1;
+    }
+
+    Ok(summary)
+}
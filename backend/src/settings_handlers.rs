@@ -0,0 +1,71 @@
+use axum::extract::State;
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Form, Json};
+use http::header::CONTENT_DISPOSITION;
+use hyper::Body;
+use tera::Context;
+use tracing::error;
+
+use crate::db::Store;
+use crate::error::AppError;
+use crate::flash::IncomingFlash;
+use crate::models::export::DeleteAccount;
+use crate::models::user::Claims;
+use crate::template::TEMPLATES;
+use crate::user_handlers::{logout, verify_password};
+
+// Settings ------------------------------------------------------------------------------------------------------------
+pub async fn settings_page(
+    State(am_database): State<Store>,
+    claims: Claims,
+    flash: IncomingFlash,
+) -> Result<(IncomingFlash, Html<String>), AppError> {
+    let profile = am_database
+        .get_user_summary_by_email(claims.email.clone())
+        .await?;
+
+    let mut context = Context::new();
+    context.insert("flash", &flash.0);
+    context.insert("claims", &claims);
+    context.insert("profile", &profile);
+
+    let rendered = TEMPLATES
+        .render("settings.html", &context)
+        .unwrap_or_else(|err| {
+            error!("Template rendering error: {}", err);
+            panic!()
+        });
+    Ok((flash, Html(rendered)))
+}
+
+pub async fn export_account(
+    State(am_database): State<Store>,
+    claims: Claims,
+) -> Result<Response, AppError> {
+    let export = am_database.export_account(claims.email).await?;
+
+    Ok((
+        [(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"astrolocker_export.json\"",
+        )],
+        Json(export),
+    )
+        .into_response())
+}
+
+/// Asks for the password again so a forgotten logged in browser can't be used to delete an account
+pub async fn delete_account(
+    State(am_database): State<Store>,
+    claims: Claims,
+    Form(confirmation): Form<DeleteAccount>,
+) -> Result<Response<Body>, AppError> {
+    let user = am_database.get_user(&claims.email).await?;
+    if !verify_password(&user.password, &confirmation.password)? {
+        return Err(AppError::InvalidPassword);
+    }
+
+    am_database.delete_user_by_email(claims.email).await?;
+
+    logout().await
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Config;
+use crate::db::Store;
+use crate::repository::Repository;
+
+/// What every handler can get at. Handlers ask for the part they need, `State<Store>`,
+/// `State<Arc<dyn Repository>>` or `State<Arc<Config>>`, rather than all of it.
+#[derive(Clone)]
+pub struct AppState {
+    /// Can sit on a pool from `db::new_lazy_pool`, which only connects once a `Store` handler runs
+    pub store: Store,
+    /// The same database as `store` in production, swappable for an in-memory one
+    pub repo: Arc<dyn Repository>,
+    pub config: Arc<Config>,
+    /// Cancelled when the server starts shutting down, for handlers that would otherwise hold it open
+    pub shutdown: CancellationToken,
+}
+
+impl FromRef<AppState> for Store {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Repository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.repo.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for CancellationToken {
+    fn from_ref(state: &AppState) -> Self {
+        state.shutdown.clone()
+    }
+}
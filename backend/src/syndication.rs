@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+
+use crate::models::syndication::SyndicationEntry;
+
+/// How many entries we put in a single RSS or Atom feed
+pub const SYNDICATION_LIMIT: i64 = 50;
+
+/// Everything about a feed that isn't one of its entries
+pub struct FeedInfo {
+    pub title: String,
+    pub description: String,
+    /// `PUBLIC_BASE_URL`, e.g. `https://astrolocker.example.com`
+    pub base_url: String,
+    /// Path of the feed itself, e.g. `/feed.rss`
+    pub self_path: String,
+}
+
+pub fn render_rss(info: &FeedInfo, entries: &[SyndicationEntry]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str(r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel>"#);
+    xml.push_str(&format!(
+        "<title>{}</title><link>{}/</link><description>{}</description>",
+        encode_text(&info.title),
+        encode_text(&info.base_url),
+        encode_text(&info.description),
+    ));
+    xml.push_str(&format!(
+        r#"<atom:link href="{}{}" rel="self" type="application/rss+xml"/>"#,
+        encode_double_quoted_attribute(&info.base_url),
+        encode_double_quoted_attribute(&info.self_path),
+    ));
+    xml.push_str(&format!(
+        "<lastBuildDate>{}</lastBuildDate>",
+        last_updated(entries).to_rfc2822()
+    ));
+
+    for entry in entries {
+        let link = post_link(&info.base_url, entry);
+        xml.push_str("<item>");
+        xml.push_str(&format!(
+            r#"<title>{}</title><link>{}</link><guid isPermaLink="true">{}</guid>"#,
+            encode_text(&entry.post.title),
+            encode_text(&link),
+            encode_text(&link),
+        ));
+        xml.push_str(&format!(
+            "<pubDate>{}</pubDate><description>{}</description>",
+            entry.added_on.to_rfc2822(),
+            encode_text(&entry.post.explanation),
+        ));
+        if let Some(mime_type) = image_mime_type(&entry.post.img_url) {
+            xml.push_str(&format!(
+                r#"<enclosure url="{}" type="{}" length="0"/>"#,
+                encode_double_quoted_attribute(&entry.post.img_url),
+                mime_type,
+            ));
+        }
+        xml.push_str("</item>");
+    }
+
+    xml.push_str("</channel></rss>");
+    xml
+}
+
+pub fn render_atom(info: &FeedInfo, entries: &[SyndicationEntry]) -> String {
+    let self_url = format!("{}{}", info.base_url, info.self_path);
+
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    xml.push_str(&format!(
+        "<title>{}</title><subtitle>{}</subtitle><id>{}</id>",
+        encode_text(&info.title),
+        encode_text(&info.description),
+        encode_text(&self_url),
+    ));
+    xml.push_str(&format!(
+        r#"<link rel="self" type="application/atom+xml" href="{}"/><link href="{}/"/>"#,
+        encode_double_quoted_attribute(&self_url),
+        encode_double_quoted_attribute(&info.base_url),
+    ));
+    xml.push_str(&format!(
+        "<updated>{}</updated><author><name>AstroLocker</name></author>",
+        last_updated(entries).to_rfc3339()
+    ));
+
+    for entry in entries {
+        let link = post_link(&info.base_url, entry);
+        xml.push_str("<entry>");
+        xml.push_str(&format!(
+            r#"<title>{}</title><id>{}</id><link href="{}"/>"#,
+            encode_text(&entry.post.title),
+            encode_text(&link),
+            encode_double_quoted_attribute(&link),
+        ));
+        xml.push_str(&format!(
+            r#"<updated>{}</updated><summary type="text">{}</summary>"#,
+            entry.added_on.to_rfc3339(),
+            encode_text(&entry.post.explanation),
+        ));
+        if let Some(mime_type) = image_mime_type(&entry.post.img_url) {
+            xml.push_str(&format!(
+                r#"<link rel="enclosure" type="{}" href="{}"/>"#,
+                mime_type,
+                encode_double_quoted_attribute(&entry.post.img_url),
+            ));
+        }
+        xml.push_str("</entry>");
+    }
+
+    xml.push_str("</feed>");
+    xml
+}
+
+/// `/posts/{id}` is JSON, so entries point at the post on the home page instead
+fn post_link(base_url: &str, entry: &SyndicationEntry) -> String {
+    format!("{}/#post-{}", base_url, entry.post.id)
+}
+
+/// Entries come newest first, an empty feed was last updated now
+fn last_updated(entries: &[SyndicationEntry]) -> DateTime<Utc> {
+    entries
+        .first()
+        .map(|entry| entry.added_on)
+        .unwrap_or_else(Utc::now)
+}
+
+/// Some APODs are videos (youtube links) rather than images, those don't get an enclosure
+fn image_mime_type(img_url: &str) -> Option<&'static str> {
+    let extension = img_url.rsplit('.').next()?.to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use http::header::CONTENT_TYPE;
+
+use crate::config::Config;
+use crate::db::Store;
+use crate::error::AppError;
+use crate::syndication::{render_atom, render_rss, FeedInfo, SYNDICATION_LIMIT};
+
+const RSS_CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+// Syndication ---------------------------------------------------------------------------------------------------------
+pub async fn all_posts_rss(
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+) -> Result<Response, AppError> {
+    let entries = am_database.get_newest_entries(SYNDICATION_LIMIT).await?;
+    let info = all_posts_info(&config, "/feed.rss");
+    Ok((
+        [(CONTENT_TYPE, RSS_CONTENT_TYPE)],
+        render_rss(&info, &entries),
+    )
+        .into_response())
+}
+
+pub async fn all_posts_atom(
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+) -> Result<Response, AppError> {
+    let entries = am_database.get_newest_entries(SYNDICATION_LIMIT).await?;
+    let info = all_posts_info(&config, "/feed.atom");
+    Ok((
+        [(CONTENT_TYPE, ATOM_CONTENT_TYPE)],
+        render_atom(&info, &entries),
+    )
+        .into_response())
+}
+
+pub async fn user_likes_rss(
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+    Path(user_id): Path<i32>,
+) -> Result<Response, AppError> {
+    let email = am_database.get_user_email_by_id(user_id).await?;
+    let entries = am_database
+        .get_user_liked_entries(user_id, SYNDICATION_LIMIT)
+        .await?;
+    let info = user_likes_info(&config, &email, format!("/users/{}/feed.rss", user_id));
+    Ok((
+        [(CONTENT_TYPE, RSS_CONTENT_TYPE)],
+        render_rss(&info, &entries),
+    )
+        .into_response())
+}
+
+pub async fn user_likes_atom(
+    State(am_database): State<Store>,
+    State(config): State<Arc<Config>>,
+    Path(user_id): Path<i32>,
+) -> Result<Response, AppError> {
+    let email = am_database.get_user_email_by_id(user_id).await?;
+    let entries = am_database
+        .get_user_liked_entries(user_id, SYNDICATION_LIMIT)
+        .await?;
+    let info = user_likes_info(&config, &email, format!("/users/{}/feed.atom", user_id));
+    Ok((
+        [(CONTENT_TYPE, ATOM_CONTENT_TYPE)],
+        render_atom(&info, &entries),
+    )
+        .into_response())
+}
+
+fn all_posts_info(config: &Config, self_path: &str) -> FeedInfo {
+    FeedInfo {
+        title: "AstroLocker".to_string(),
+        description: "The newest Astronomy Pictures of the Day added to the locker".to_string(),
+        base_url: config.public_base_url.clone(),
+        self_path: self_path.to_string(),
+    }
+}
+
+fn user_likes_info(config: &Config, email: &str, self_path: String) -> FeedInfo {
+    FeedInfo {
+        title: format!("AstroLocker - Liked by {}", email),
+        description: format!("Astronomy Pictures of the Day that {} liked", email),
+        base_url: config.public_base_url.clone(),
+        self_path,
+    }
+}
@@ -0,0 +1,68 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
+
+use crate::db::Store;
+
+/// How often the "people who liked this also liked" table gets rebuilt from the votes
+pub const RECOMMENDATION_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// The workers running next to the server. They all watch the same token, so cancelling it on shutdown
+/// stops every one of them, and the tracker lets shutdown wait for them to finish what they're doing.
+#[derive(Clone, Default)]
+pub struct Background {
+    shutdown: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl Background {
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    /// Cancelled once the server starts shutting down
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Cancels the token, if a signal hasn't already, and waits up to `timeout` for the workers to stop
+    pub async fn stop(&self, timeout: Duration) {
+        self.shutdown.cancel();
+        self.tracker.close();
+        if tokio::time::timeout(timeout, self.tracker.wait())
+            .await
+            .is_err()
+        {
+            warn!(
+                "{} background task(s) still running after {:?}, leaving them behind",
+                self.tracker.len(),
+                timeout
+            );
+        }
+    }
+}
+
+// Background Tasks ----------------------------------------------------------------------------------------------------
+pub async fn refresh_recommendations(am_database: Store, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(RECOMMENDATION_REFRESH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                info!("Stopped refreshing post recommendations");
+                return;
+            }
+            _ = interval.tick() => {}
+        }
+
+        match am_database.refresh_post_similarities().await {
+            Ok(()) => info!("Refreshed post recommendations"),
+            Err(err) => error!("Could not refresh post recommendations: {:?}", err),
+        }
+    }
+}
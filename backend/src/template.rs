@@ -5,7 +5,7 @@ lazy_static::lazy_static! {
         let mut tera = match Tera::new("templates/**/*") {
             Ok(t) => t,
             Err(e) => {
-                println!("Parsing error(s): {}", e);
+                tracing::error!("Parsing error(s): {}", e);
                 ::std::process::exit(1);
             }
         };
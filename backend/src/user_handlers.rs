@@ -1,4 +1,5 @@
-use argon2::Config;
+use std::sync::Arc;
+
 use axum::extract::State;
 use axum::response::Response;
 use axum::Form;
@@ -7,23 +8,34 @@ use http::{HeaderValue, StatusCode};
 use hyper::Body;
 use jsonwebtoken::Header;
 
-use crate::db::Store;
-use crate::error::AppError;
-use crate::get_timestamp_after_8_hours;
-use crate::models::user::{Claims, User, UserSignup, KEYS};
+use crate::config::Config;
+use crate::error::{AppError, FieldError};
+use crate::get_timestamp_after_hours;
+use crate::metrics::METRICS;
+use crate::models::user::{Claims, User, UserSignup};
+use crate::repository::Repository;
 
 // User ----------------------------------------------------------------------------------------------------------------
 pub async fn register(
-    State(database): State<Store>,
+    State(database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
     Form(mut credentials): Form<UserSignup>,
 ) -> Result<Response<Body>, AppError> {
     // We should also check to validate other things at some point like email address being in right format
-    if credentials.email.is_empty() || credentials.password.is_empty() {
-        return Err(AppError::MissingCredentials);
+    let mut problems = Vec::new();
+    if credentials.email.is_empty() {
+        problems.push(FieldError::new("email", "Enter an email address"));
     }
-
-    if credentials.password != credentials.confirm_password {
-        return Err(AppError::MissingCredentials);
+    if credentials.password.is_empty() {
+        problems.push(FieldError::new("password", "Choose a password"));
+    } else if credentials.password != credentials.confirm_password {
+        problems.push(FieldError::new(
+            "confirm_password",
+            "The passwords don't match",
+        ));
+    }
+    if !problems.is_empty() {
+        return Err(AppError::Validation(problems));
     }
 
     // Check to see if there is already a user in the database with the given email address
@@ -35,87 +47,67 @@ pub async fn register(
 
     // Here we're assured that our credentials are valid and the user doesn't already exist
     // hash their password
-    let hash_config = Config::default();
-    let salt = std::env::var("SALT").expect("Missing SALT");
-    let hashed_password = match argon2::hash_encoded(
-        credentials.password.as_bytes(),
-        // If you'd like unique salts per-user, simply pass &[] and argon will generate them for you
-        salt.as_bytes(),
-        &hash_config,
-    ) {
-        Ok(result) => result,
-        Err(_) => {
-            return Err(AppError::Any(anyhow::anyhow!("Password hashing failed")));
-        }
-    };
-
-    credentials.password = hashed_password;
+    credentials.password = hash_password(&credentials.password, &config.salt)?;
 
-    let _ = database.create_user(credentials.clone()).await?;
+    database.create_user(credentials.clone()).await?;
+    METRICS.record_signup();
 
     // at this point we've authenticated the user's identity
-    // create JWT to return
-    let claims = Claims {
-        id: 0,
-        email: credentials.email.to_owned(),
-        exp: get_timestamp_after_8_hours(),
-    };
-
-    let token = jsonwebtoken::encode(&Header::default(), &claims, &KEYS.encoding)
-        .map_err(|_| AppError::MissingCredentials)?;
-
-    let cookie = cookie::Cookie::build("jwt", token).http_only(true).finish();
-
-    let mut response = Response::builder()
-        .status(StatusCode::FOUND)
-        .body(Body::empty())
-        .unwrap();
+    database.record_login(credentials.email.clone()).await?;
+    let id = database
+        .get_user_id_by_email(credentials.email.clone())
+        .await?;
 
-    response
-        .headers_mut()
-        .insert(LOCATION, HeaderValue::from_static("/"));
-    response.headers_mut().insert(
-        SET_COOKIE,
-        HeaderValue::from_str(&cookie.to_string()).unwrap(),
-    );
-
-    Ok(response)
+    login_response(&config, id, credentials.email)
 }
 
 pub async fn login(
-    State(database): State<Store>,
+    State(database): State<Arc<dyn Repository>>,
+    State(config): State<Arc<Config>>,
     Form(creds): Form<User>,
 ) -> Result<Response<Body>, AppError> {
     if creds.email.is_empty() || creds.password.is_empty() {
         return Err(AppError::MissingCredentials);
     }
 
-    let existing_user = database.get_user(&creds.email).await?;
+    let existing_user = database
+        .get_user(&creds.email)
+        .await
+        .map_err(|err| match err {
+            AppError::NotFound => AppError::UserDoesNotExist,
+            err => err,
+        })?;
 
-    let is_password_correct =
-        match argon2::verify_encoded(&*existing_user.password, creds.password.as_bytes()) {
-            Ok(result) => result,
-            Err(_) => {
-                return Err(AppError::InternalServerError);
-            }
-        };
+    let is_password_correct = verify_password(&existing_user.password, &creds.password)?;
 
     if !is_password_correct {
         return Err(AppError::InvalidPassword);
     }
 
     // at this point we've authenticated the user's identity
+    database.record_login(creds.email.clone()).await?;
+    let id = database.get_user_id_by_email(creds.email.clone()).await?;
+
+    login_response(&config, id, creds.email)
+}
+
+/// Redirects home with the JWT cookie set, lasting and flagged however the config says
+fn login_response(config: &Config, id: i32, email: String) -> Result<Response<Body>, AppError> {
     // create JWT to return
     let claims = Claims {
-        id: 0,
-        email: creds.email.to_owned(),
-        exp: get_timestamp_after_8_hours(),
+        id,
+        email,
+        exp: get_timestamp_after_hours(config.token_lifetime_hours),
     };
 
-    let token = jsonwebtoken::encode(&Header::default(), &claims, &KEYS.encoding)
+    let token = jsonwebtoken::encode(&Header::default(), &claims, &config.keys().encoding)
         .map_err(|_| AppError::MissingCredentials)?;
 
-    let cookie = cookie::Cookie::build("jwt", token).http_only(true).finish();
+    let cookie = cookie::Cookie::build("jwt", token)
+        .http_only(true)
+        .secure(config.cookie_secure)
+        .same_site(config.cookie_same_site)
+        .finish();
 
     let mut response = Response::builder()
         .status(StatusCode::FOUND)
@@ -148,3 +140,22 @@ pub async fn logout() -> Result<Response<Body>, AppError> {
         .insert(LOCATION, HeaderValue::from_static("/"));
     Ok(response)
 }
+
+/// Also used by the admin CLI, which creates accounts without going through the signup form
+pub fn hash_password(password: &str, salt: &str) -> Result<String, AppError> {
+    let hash_config = argon2::Config::default();
+    match argon2::hash_encoded(
+        password.as_bytes(),
+        // If you'd like unique salts per-user, simply pass &[] and argon will generate them for you
+        salt.as_bytes(),
+        &hash_config,
+    ) {
+        Ok(result) => Ok(result),
+        Err(_) => Err(AppError::Any(anyhow::anyhow!("Password hashing failed"))),
+    }
+}
+
+pub fn verify_password(hashed_password: &str, password: &str) -> Result<bool, AppError> {
+    argon2::verify_encoded(hashed_password, password.as_bytes())
+        .map_err(|_| AppError::InternalServerError)
+}
@@ -1,58 +1,91 @@
+use std::sync::Arc;
+
 use axum::extract::State;
 use axum::response::Response;
 use axum::{Form, Json};
 use hyper::Body;
 
-use crate::db::Store;
 use crate::error::AppError;
 use crate::handlers::create_response_path;
-use crate::models::vote::{CreateVote, Vote};
+use crate::metrics::METRICS;
+use crate::models::user::Claims;
+use crate::models::vote::{CreateVote, VoteOnPost, VoteStatus};
+use crate::repository::Repository;
 
 // Votes ---------------------------------------------------------------------------------------------------------------
-pub async fn create_vote(
-    State(mut am_database): State<Store>,
-    Json(vote): Json<CreateVote>,
-) -> Result<Json<Vote>, AppError> {
-    let new_vote = CreateVote {
-        post_id: vote.post_id,
-        user_id: vote.user_id,
-    };
-    let finished_vote = am_database.create_vote(new_vote).await?;
-
-    Ok(Json(finished_vote))
-}
-
+/// Likes as whoever is logged in, like the JSON routes, the form only says which post
 pub async fn create_vote_from_form(
-    State(mut am_database): State<Store>,
-    Form(vote): Form<CreateVote>,
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
+    Form(vote): Form<VoteOnPost>,
 ) -> Result<Response<Body>, AppError> {
-    let new_vote = CreateVote {
-        post_id: vote.post_id,
-        user_id: vote.user_id,
-    };
-    am_database.create_vote(new_vote).await?;
-    let response = create_response_path();
+    let user_id = am_database.get_user_id_by_email(claims.email).await?;
+    am_database
+        .create_vote(CreateVote {
+            post_id: vote.post_id,
+            user_id,
+        })
+        .await?;
+    METRICS.record_vote(true);
+    let response = create_response_path()?;
 
     Ok(response)
 }
 
 pub async fn delete_vote_from_form(
-    State(mut am_database): State<Store>,
-    Form(vote): Form<CreateVote>,
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
+    Form(vote): Form<VoteOnPost>,
 ) -> Result<Response<Body>, AppError> {
-    let old_vote = CreateVote {
-        post_id: vote.post_id,
-        user_id: vote.user_id,
-    };
-    am_database.delete_vote(old_vote).await?;
-    let response = create_response_path();
+    let user_id = am_database.get_user_id_by_email(claims.email).await?;
+    am_database
+        .delete_vote(CreateVote {
+            post_id: vote.post_id,
+            user_id,
+        })
+        .await?;
+    METRICS.record_vote(false);
+    let response = create_response_path()?;
     Ok(response)
 }
 
-pub async fn get_votes_for_post(
-    State(mut am_database): State<Store>,
-    query: i32,
-) -> Result<Json<i64>, AppError> {
-    let num_votes = am_database.get_number_of_votes_for_post(query).await?;
-    Ok(Json(num_votes))
+/// Likes as whoever is logged in, the body only says which post
+pub async fn create_vote_json(
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
+    Json(vote): Json<VoteOnPost>,
+) -> Result<Json<VoteStatus>, AppError> {
+    let user_id = am_database.get_user_id_by_email(claims.email).await?;
+    let post_id = vote.post_id;
+    am_database
+        .create_vote(CreateVote { post_id, user_id })
+        .await?;
+    METRICS.record_vote(true);
+    let num_likes = am_database.get_number_of_votes_for_post(post_id.0).await?;
+
+    Ok(Json(VoteStatus {
+        post_id,
+        num_likes,
+        already_liked: true,
+    }))
+}
+
+pub async fn delete_vote_json(
+    State(am_database): State<Arc<dyn Repository>>,
+    claims: Claims,
+    Json(vote): Json<VoteOnPost>,
+) -> Result<Json<VoteStatus>, AppError> {
+    let user_id = am_database.get_user_id_by_email(claims.email).await?;
+    let post_id = vote.post_id;
+    am_database
+        .delete_vote(CreateVote { post_id, user_id })
+        .await?;
+    METRICS.record_vote(false);
+    let num_likes = am_database.get_number_of_votes_for_post(post_id.0).await?;
+
+    Ok(Json(VoteStatus {
+        post_id,
+        num_likes,
+        already_liked: false,
+    }))
 }
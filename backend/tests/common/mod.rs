@@ -0,0 +1,289 @@
+//! Shared setup for the HTTP tests. Every test gets its own database from `#[sqlx::test]`, the app
+//! from `main_routes::app` on top of it, and a fake APOD server so nothing talks to NASA. Tests that
+//! only need the `Repository` routes can run on an `InMemoryRepository` instead, without Postgres.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::Query;
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Json, Router};
+use backend::config::Config;
+use backend::db::{self, Store};
+use backend::models::role::Role;
+use backend::repository::{InMemoryRepository, Repository};
+use backend::routes::main_routes;
+use backend::state::AppState;
+use http::header::{CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use http::{Method, Request, StatusCode};
+use hyper::Body;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+pub struct TestApp {
+    router: Router,
+    pub store: Store,
+    /// The same data as `store`, unless the app runs on an `InMemoryRepository`
+    pub repo: Arc<dyn Repository>,
+    /// The app's shutdown token, cancelling it ends the live event streams
+    pub shutdown: CancellationToken,
+    /// What the app was built with, e.g. for hashing passwords the way it does
+    pub config: Arc<Config>,
+}
+
+/// The `jwt` cookie of a logged in user, sent back with every request made as them
+#[derive(Clone, Debug)]
+pub struct Session {
+    cookie: String,
+}
+
+impl Session {
+    /// The `Cookie` header that logs a request in, for requests built by hand and passed to `send`
+    pub fn cookie(&self) -> &str {
+        &self.cookie
+    }
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: http::HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn location(&self) -> Option<&str> {
+        self.headers
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body)
+            .unwrap_or_else(|err| panic!("Expected JSON, got {:?}: {}", self.body, err))
+    }
+
+    /// The `name=value` part of the `jwt` cookie this response sets, if it sets one
+    pub fn jwt_cookie(&self) -> Option<String> {
+        self.headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| value.split(';').next())
+            .find(|cookie| cookie.starts_with("jwt="))
+            .map(|cookie| cookie.to_string())
+    }
+}
+
+impl TestApp {
+    pub async fn new(pool: PgPool) -> Self {
+        let apod_url = spawn_fake_apod().await;
+        let config = test_config(&apod_url);
+        let config = Arc::new(config);
+        let shutdown = CancellationToken::new();
+        let router = main_routes::app(pool.clone(), config.clone(), shutdown.clone());
+
+        let store = Store::with_pool(pool);
+        Self {
+            router,
+            repo: Arc::new(store.clone()),
+            store,
+            shutdown,
+            config,
+        }
+    }
+
+    /// The app on top of `repo`. `store` never connects, so the routes `backend::repository` lists as
+    /// still taking `Store` fail.
+    pub async fn in_memory(repo: Arc<InMemoryRepository>) -> Self {
+        let apod_url = spawn_fake_apod().await;
+        let config = test_config(&apod_url);
+        let store = Store::with_pool(db::new_lazy_pool(&config).unwrap());
+        let config = Arc::new(config);
+        let shutdown = CancellationToken::new();
+        let router = main_routes::router(AppState {
+            store: store.clone(),
+            repo: repo.clone(),
+            config: config.clone(),
+            shutdown: shutdown.clone(),
+        });
+
+        Self {
+            router,
+            store,
+            repo,
+            shutdown,
+            config,
+        }
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        session: Option<&Session>,
+        content_type: Option<&str>,
+        body: Body,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(session) = session {
+            request = request.header(COOKIE, &session.cookie);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+
+        self.send(request.body(body).unwrap()).await
+    }
+
+    /// For requests the helpers above can't build, e.g. with extra headers
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.open(request).await;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        TestResponse {
+            status,
+            headers,
+            body: String::from_utf8_lossy(&body).to_string(),
+        }
+    }
+
+    /// The response before its body is read, for streams that only end when the app shuts down
+    pub async fn open(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    pub async fn get(&self, path: &str, session: Option<&Session>) -> TestResponse {
+        self.request(Method::GET, path, session, None, Body::empty())
+            .await
+    }
+
+    pub async fn post_form(
+        &self,
+        path: &str,
+        form: &[(&str, &str)],
+        session: Option<&Session>,
+    ) -> TestResponse {
+        let body = serde_urlencoded::to_string(form).unwrap();
+        self.request(
+            Method::POST,
+            path,
+            session,
+            Some("application/x-www-form-urlencoded"),
+            Body::from(body),
+        )
+        .await
+    }
+
+    pub async fn post_json(
+        &self,
+        path: &str,
+        json: Value,
+        session: Option<&Session>,
+    ) -> TestResponse {
+        self.request(
+            Method::POST,
+            path,
+            session,
+            Some("application/json"),
+            Body::from(json.to_string()),
+        )
+        .await
+    }
+
+    /// Signs up through the form and keeps the cookie it hands back
+    pub async fn register(&self, email: &str, password: &str) -> Session {
+        let response = self
+            .post_form(
+                "/users",
+                &[
+                    ("email", email),
+                    ("password", password),
+                    ("confirm_password", password),
+                ],
+                None,
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        session_from(&response)
+    }
+
+    pub async fn login(&self, email: &str, password: &str) -> TestResponse {
+        self.post_form("/login", &[("email", email), ("password", password)], None)
+            .await
+    }
+
+    /// Registers a user and makes them `role` straight in the database, the way the admin CLI does
+    pub async fn user_with_role(&self, email: &str, role: Role) -> Session {
+        let session = self.register(email, "password").await;
+        self.repo
+            .set_user_role_by_email("admin-cli".to_string(), None, email.to_string(), role)
+            .await
+            .unwrap();
+        session
+    }
+
+    pub async fn user_id(&self, email: &str) -> i32 {
+        self.repo
+            .get_user_id_by_email(email.to_string())
+            .await
+            .unwrap()
+    }
+}
+
+pub fn session_from(response: &TestResponse) -> Session {
+    let cookie = response
+        .jwt_cookie()
+        .unwrap_or_else(|| panic!("No jwt cookie in {:?}", response.headers));
+    Session { cookie }
+}
+
+fn test_config(apod_url: &str) -> Config {
+    let values: HashMap<String, String> = [
+        ("API_HOST", "127.0.0.1"),
+        ("API_PORT", "3000"),
+        // Only used to build the pool, the tests hand `app` one that already exists
+        ("DATABASE_URL", "postgres://unused"),
+        ("JWT_SECRET", "test-secret"),
+        ("SALT", "test-salt-that-is-long-enough"),
+        ("NASA_API_KEY", "test-key"),
+        ("NASA_BASE_URL", apod_url),
+        ("PUBLIC_BASE_URL", "https://astro.test/"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+
+    Config::from_values(values, Vec::new()).unwrap()
+}
+
+/// Answers like the APOD API for any date in 2023, and with NASA's 400 error for anything else
+async fn spawn_fake_apod() -> String {
+    async fn apod(Query(query): Query<HashMap<String, String>>) -> Json<Value> {
+        let date = query.get("date").cloned().unwrap_or_default();
+        if !date.starts_with("2023-") {
+            return Json(
+                json!({ "code": 400, "msg": "Date must be between Jun 16, 1995 and today" }),
+            );
+        }
+        Json(json!({
+            "date": date,
+            "title": format!("Fake APOD for {}", date),
+            "explanation": "Served by the test suite",
+            "url": format!("https://example.com/{}.jpg", date),
+            "media_type": "image",
+        }))
+    }
+
+    let app = Router::new().route("/", get(apod));
+    let server =
+        axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    url
+}
@@ -0,0 +1,1205 @@
+//! Drives the whole app over HTTP, one fresh database per test. Needs `DATABASE_URL` to point at a
+//! Postgres server the tests are allowed to create databases on.
+
+mod common;
+
+use backend::db::STATS_DAYS;
+use backend::error::AppError;
+use backend::models::post::CreatePost;
+use backend::models::report::{ReportResolution, ReportTarget};
+use backend::models::role::Role;
+use backend::models::similarity::SimilarPost;
+use backend::models::user::{BulkAction, BulkUserAction, UserSignup};
+use backend::models::vote::CreateVote;
+use backend::repository::{InMemoryRepository, Repository};
+use backend::seed::{demo_fixture, seed};
+use chrono::{Duration, Utc};
+use common::{session_from, TestApp, TestResponse};
+use http::header::CONTENT_TYPE;
+use http::{Request, StatusCode};
+use hyper::body::HttpBody;
+use hyper::Body;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+// Probes --------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn probes(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    assert_eq!(app.get("/healthz", None).await.status, StatusCode::OK);
+
+    let ready = app.get("/readyz", None).await;
+    assert_eq!(ready.status, StatusCode::OK, "{}", ready.body);
+    assert_eq!(ready.json()["ready"], true);
+
+    let version = app.get("/version", None).await.json();
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(version["migration"], version["latest_migration"]);
+
+    // Counters are process-wide and the tests share them, so only check the shape
+    let metrics = app.get("/metrics", None).await;
+    assert_eq!(metrics.status, StatusCode::OK);
+    assert!(metrics
+        .body
+        .contains("http_requests_total{method=\"GET\",route=\"/healthz\",status=\"200\"}"));
+    assert!(metrics.body.contains("# TYPE db_pool_connections gauge"));
+}
+
+#[tokio::test]
+async fn readyz_hides_why_the_database_is_down() {
+    // The in-memory app's store points at a database that doesn't exist
+    let app = TestApp::in_memory(Arc::new(InMemoryRepository::new())).await;
+
+    let ready = app.get("/readyz", None).await;
+    assert_eq!(ready.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready.json()["database"], "unavailable");
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn request_ids(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let generated = app.get("/healthz", None).await;
+    let request_id = generated.headers.get("x-request-id").unwrap();
+    assert!(uuid::Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+
+    let request = Request::get("/healthz")
+        .header("x-request-id", "from-the-proxy")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.headers["x-request-id"], "from-the-proxy");
+}
+
+// Users ---------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn register_and_login(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let session = app.register("ada@example.com", "hunter22").await;
+    assert_eq!(
+        app.get("/settings", Some(&session)).await.status,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.get("/settings", None).await.status,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let wrong_password = app.login("ada@example.com", "nope").await;
+    assert_eq!(wrong_password.status, StatusCode::UNAUTHORIZED);
+    assert!(wrong_password.jwt_cookie().is_none());
+
+    let unknown = app.login("nobody@example.com", "hunter22").await;
+    assert_eq!(unknown.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown.json()["code"], "user_does_not_exist");
+
+    let response = app.login("ada@example.com", "hunter22").await;
+    assert_eq!(response.status, StatusCode::FOUND);
+    assert_eq!(response.location(), Some("/"));
+    let session = session_from(&response);
+    let settings = app.get("/settings", Some(&session)).await;
+    assert!(settings.body.contains("ada@example.com"));
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn register_twice_is_rejected(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.register("ada@example.com", "hunter22").await;
+
+    let response = app
+        .post_form(
+            "/users",
+            &[
+                ("email", "ada@example.com"),
+                ("password", "other"),
+                ("confirm_password", "other"),
+            ],
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.json()["code"], "user_already_exists");
+    assert!(response.jwt_cookie().is_none());
+
+    // A signup that loses a race past that check trips the unique constraint, also a conflict
+    let raced = app
+        .store
+        .create_user(UserSignup {
+            email: "ada@example.com".to_string(),
+            password: "hashed".to_string(),
+            confirm_password: "hashed".to_string(),
+        })
+        .await;
+    assert!(matches!(raced, Err(AppError::Conflict(_))), "{:?}", raced);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn register_reports_every_bad_field(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let response = app
+        .post_form(
+            "/users",
+            &[
+                ("email", ""),
+                ("password", "hunter22"),
+                ("confirm_password", "hunter23"),
+            ],
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body = response.json();
+    assert_eq!(body["code"], "validation_failed");
+    let fields: Vec<_> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["email", "confirm_password"]);
+}
+
+// Settings ------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn export_only_has_your_own_data(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let ada = app.register("ada@example.com", "hunter22").await;
+    let bob = app.register("bob@example.com", "hunter22").await;
+    app.post_form("/get_apod", &[("query_string", "2023-05-01")], Some(&ada))
+        .await;
+    app.post_form("/get_apod", &[("query_string", "2023-05-02")], Some(&bob))
+        .await;
+    let posts = app.get("/posts", None).await.json();
+    for (session, post) in [(&ada, &posts[0]), (&bob, &posts[1])] {
+        let vote = json!({ "post_id": post["id"] });
+        let liked = app.post_json("/votes/json", vote, Some(session)).await;
+        assert_eq!(liked.status, StatusCode::OK, "{}", liked.body);
+    }
+    app.post_form("/follow", &[("email", "ada@example.com")], Some(&bob))
+        .await;
+
+    let response = app.get("/settings/export", Some(&ada)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let export = response.json();
+    let ada_id = app.user_id("ada@example.com").await;
+    assert_eq!(export["profile"]["email"], "ada@example.com");
+    assert_eq!(export["likes"].as_array().unwrap().len(), 1);
+    assert_eq!(export["likes"][0]["post_id"], posts[0]["id"]);
+    assert_eq!(export["following"], json!([]));
+    assert_eq!(export["followers"], json!(["bob@example.com"]));
+    let notifications = export["notifications"].as_array().unwrap();
+    assert_eq!(notifications.len(), 1);
+    assert!(notifications
+        .iter()
+        .all(|notification| notification["user_id"] == ada_id));
+    assert!(!response.body.contains("hunter22"));
+
+    let response = app.get("/settings/export", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn deleting_your_account_needs_your_password(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let ada = app.register("ada@example.com", "hunter22").await;
+    app.register("bob@example.com", "hunter22").await;
+
+    let response = app
+        .post_form("/settings/delete", &[("password", "wrong")], Some(&ada))
+        .await;
+    assert_eq!(
+        response.status,
+        StatusCode::UNAUTHORIZED,
+        "{}",
+        response.body
+    );
+    assert_eq!(response.json()["code"], "invalid_password");
+    assert!(app.repo.get_user("ada@example.com").await.is_ok());
+
+    let response = app
+        .post_form("/settings/delete", &[("password", "hunter22")], Some(&ada))
+        .await;
+    assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+    assert_eq!(response.jwt_cookie().as_deref(), Some("jwt="));
+    assert!(matches!(
+        app.repo.get_user("ada@example.com").await,
+        Err(AppError::NotFound)
+    ));
+    assert!(app.repo.get_user("bob@example.com").await.is_ok());
+    let login = app.login("ada@example.com", "hunter22").await;
+    assert_ne!(login.status, StatusCode::FOUND, "{}", login.body);
+}
+
+// Seeding -------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn seeding_loads_the_demo_data_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let fixture = demo_fixture().unwrap();
+    let (users, posts, votes) = (
+        fixture.users.len(),
+        fixture.posts.len(),
+        fixture.votes.len(),
+    );
+    let owner = fixture.users[0].email.clone();
+    let ada = &fixture.users[2];
+    let (ada_email, ada_password) = (ada.email.clone(), ada.password.clone());
+
+    let summary = seed(&app.store, &app.config, fixture).await.unwrap();
+    assert_eq!(
+        (summary.users, summary.posts, summary.votes),
+        (users, posts, votes)
+    );
+    let again = seed(&app.store, &app.config, demo_fixture().unwrap())
+        .await
+        .unwrap();
+    assert_eq!((again.users, again.posts, again.votes), (0, 0, 0));
+
+    // The demo users log in with the fixture's passwords and keep its roles
+    let login = app.login(&ada_email, &ada_password).await;
+    assert_eq!(login.status, StatusCode::FOUND, "{}", login.body);
+    assert!(login.jwt_cookie().is_some());
+    let role = app.repo.get_user_role_by_email(owner).await.unwrap();
+    assert_eq!(role, Role::Owner);
+    let all_posts = app.get("/posts", None).await.json();
+    assert_eq!(all_posts.as_array().unwrap().len(), posts);
+}
+
+// Browsers ------------------------------------------------------------------------------------------------------------
+const BROWSER_ACCEPT: &str = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn failed_form_posts_redirect_back_with_a_flash(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.register("ada@example.com", "hunter22").await;
+
+    let request = Request::post("/login")
+        .header("accept", BROWSER_ACCEPT)
+        .header("host", "astro.test")
+        .header("referer", "http://astro.test/?tab=all")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from("email=ada%40example.com&password=nope"))
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::FOUND);
+    assert_eq!(response.location(), Some("/?tab=all"));
+    let flash = response.headers["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    assert!(flash.starts_with("flash="));
+    // The JSON inside is percent-encoded, quotes and spaces aren't allowed in a cookie value
+    assert!(!flash.contains(['"', ' ', ',']), "{}", flash);
+
+    let request = Request::get("/")
+        .header("cookie", &flash)
+        .body(Body::empty())
+        .unwrap();
+    let page = app.send(request).await;
+    assert!(page.body.contains("Invalid Password"), "{}", page.body);
+    let cleared = page.headers["set-cookie"].to_str().unwrap();
+    assert!(cleared.starts_with("flash=;"), "{}", cleared);
+
+    // A flash that wasn't signed by us is ignored
+    let request = Request::get("/")
+        .header(
+            "cookie",
+            "flash={\"kind\":\"error\",\"message\":\"Forged\"}",
+        )
+        .body(Body::empty())
+        .unwrap();
+    assert!(!app.send(request).await.body.contains("Forged"));
+
+    // Scripts still get JSON
+    let response = app.login("ada@example.com", "nope").await;
+    assert_eq!(response.json()["code"], "invalid_password");
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn failed_bulk_actions_go_back_to_the_user_list(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let admin = app.user_with_role("admin@example.com", Role::Admin).await;
+    app.user_with_role("owner@example.com", Role::Owner).await;
+    let bulk = |path: &str, referer: &str| {
+        Request::post(path)
+            .header("accept", BROWSER_ACCEPT)
+            .header("host", "astro.test")
+            .header("referer", referer)
+            .header("cookie", admin.cookie())
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(
+                "action=ban&email=owner%40example.com&reason=Spam&confirm=yes",
+            ))
+            .unwrap()
+    };
+
+    // The confirmation page is the response to a POST, so going back to it would be a 405
+    let response = app
+        .send(bulk(
+            "/users/bulk?return_to=/users",
+            "http://astro.test/users/bulk",
+        ))
+        .await;
+    assert_eq!(response.status, StatusCode::FOUND);
+    assert_eq!(response.location(), Some("/users"));
+    let flash = response.headers["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+
+    let request = Request::get("/users")
+        .header("cookie", format!("{}; {}", admin.cookie(), flash))
+        .body(Body::empty())
+        .unwrap();
+    let page = app.send(request).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(
+        page.body.contains("You can only act on users"),
+        "{}",
+        page.body
+    );
+
+    // Only paths on this site are followed, anything else falls back to the referer
+    let response = app
+        .send(bulk(
+            "/users/bulk?return_to=//elsewhere.test/",
+            "http://astro.test/users?page=1",
+        ))
+        .await;
+    assert_eq!(response.location(), Some("/users?page=1"));
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn browsers_get_error_pages(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let request = Request::get("/no/such/page")
+        .header("accept", BROWSER_ACCEPT)
+        .body(Body::empty())
+        .unwrap();
+    let page = app.send(request).await;
+    assert_eq!(page.status, StatusCode::NOT_FOUND);
+    assert!(page.headers["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(page.body.contains("404 Not Found"));
+
+    let json = app.get("/no/such/page", None).await;
+    assert_eq!(json.status, StatusCode::NOT_FOUND);
+    assert_eq!(json.json()["code"], "not_found");
+}
+
+// NASA ----------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn fetch_apod_creates_a_post_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("ada@example.com", "hunter22").await;
+
+    for _ in 0..2 {
+        let response = app
+            .post_form(
+                "/get_apod",
+                &[("query_string", "2023-05-01")],
+                Some(&session),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+    }
+
+    let posts = app.get("/posts", None).await.json();
+    assert_eq!(posts.as_array().unwrap().len(), 1);
+    assert_eq!(posts[0]["title"], "Fake APOD for 2023-05-01");
+    assert_eq!(posts[0]["apod_date"], "2023-05-01");
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn fetch_apod_out_of_range(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("ada@example.com", "hunter22").await;
+
+    let response = app
+        .post_form(
+            "/get_apod",
+            &[("query_string", "1990-01-01")],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.get("/posts", None).await.json(), json!([]));
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn edits_are_validated_and_audited_with_the_revision(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let admin = app.user_with_role("admin@example.com", Role::Admin).await;
+    for date in ["2023-05-01", "2023-05-02"] {
+        app.post_form("/get_apod", &[("query_string", date)], Some(&admin))
+            .await;
+    }
+    let posts = app.get("/posts", None).await.json();
+    let post_id = posts[0]["id"].as_i64().unwrap();
+    let other_title = posts[1]["title"].as_str().unwrap().to_string();
+    let edit_path = format!("/posts/{}/edit", post_id);
+
+    let fields = |response: &TestResponse| -> Vec<String> {
+        response.json()["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["field"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let empty = app
+        .post_form(
+            &edit_path,
+            &[
+                ("title", " "),
+                ("explanation", "Stars"),
+                ("img_url", "https://example.com/a.jpg"),
+                ("apod_date", ""),
+            ],
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(
+        empty.status,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "{}",
+        empty.body
+    );
+    assert_eq!(fields(&empty), ["title", "apod_date"]);
+
+    let duplicate = app
+        .post_form(
+            &edit_path,
+            &[
+                ("title", &other_title),
+                ("explanation", "Stars"),
+                ("img_url", "https://example.com/a.jpg"),
+                ("apod_date", "2023-05-01"),
+            ],
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(
+        duplicate.status,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "{}",
+        duplicate.body
+    );
+    assert_eq!(fields(&duplicate), ["title"]);
+
+    let edited = app
+        .post_form(
+            &edit_path,
+            &[
+                ("title", "Renamed"),
+                ("explanation", "Stars"),
+                ("img_url", "https://example.com/a.jpg"),
+                ("apod_date", "2023-05-01"),
+            ],
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(edited.status, StatusCode::FOUND, "{}", edited.body);
+
+    let revisions = app.store.get_post_revisions(post_id as i32).await.unwrap();
+    assert_eq!(revisions.len(), 2);
+    let audit = app.get("/audit/export", Some(&admin)).await.json();
+    let updates: Vec<_> = audit
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["action"] == "update_post")
+        .collect();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["payload"]["after"]["title"], "Renamed");
+}
+
+// Votes ---------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn vote_and_unvote(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("ada@example.com", "hunter22").await;
+    app.post_form(
+        "/get_apod",
+        &[("query_string", "2023-05-01")],
+        Some(&session),
+    )
+    .await;
+    let post_id = app.get("/posts", None).await.json()[0]["id"].clone();
+    let user_id = app.user_id("ada@example.com").await;
+    let vote = json!({ "post_id": post_id });
+
+    // Without a login there's nobody to vote as
+    let anonymous = app.post_json("/votes/json", vote.clone(), None).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+    let anonymous = app
+        .post_json("/votes/json/delete", vote.clone(), None)
+        .await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+    let post_id_field = post_id.to_string();
+    for path in ["/votes", "/votes/delete"] {
+        let anonymous = app
+            .post_form(path, &[("post_id", &post_id_field)], None)
+            .await;
+        assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED, "{}", path);
+    }
+
+    // The form can't vote for someone else, a `user_id` in it is ignored
+    let bob = app.register("bob@example.com", "hunter22").await;
+    let bob_id = app.user_id("bob@example.com").await;
+    let form = [
+        ("post_id", post_id_field.as_str()),
+        ("user_id", &user_id.to_string()),
+    ];
+    let liked = app.post_form("/votes", &form, Some(&bob)).await;
+    assert_eq!(liked.status, StatusCode::FOUND, "{}", liked.body);
+    let likes = app.get(&format!("/users/{}/posts", bob_id), None).await;
+    assert_eq!(likes.json().as_array().unwrap().len(), 1);
+    let likes = app.get(&format!("/users/{}/posts", user_id), None).await;
+    assert_eq!(likes.json(), json!([]));
+    let unliked = app.post_form("/votes/delete", &form, Some(&bob)).await;
+    assert_eq!(unliked.status, StatusCode::FOUND, "{}", unliked.body);
+
+    let liked = app
+        .post_json("/votes/json", vote.clone(), Some(&session))
+        .await;
+    assert_eq!(liked.status, StatusCode::OK, "{}", liked.body);
+    assert_eq!(liked.json()["num_likes"], 1);
+    assert_eq!(liked.json()["already_liked"], true);
+
+    // Liking twice trips the unique constraint, which comes back as a conflict
+    let again = app
+        .post_json("/votes/json", vote.clone(), Some(&session))
+        .await;
+    assert_eq!(again.status, StatusCode::CONFLICT, "{}", again.body);
+    assert_eq!(again.json()["code"], "conflict");
+
+    let likes = app.get(&format!("/users/{}/posts", user_id), None).await;
+    assert_eq!(likes.json().as_array().unwrap().len(), 1);
+
+    let unliked = app
+        .post_json("/votes/json/delete", vote, Some(&session))
+        .await;
+    assert_eq!(unliked.json()["num_likes"], 0);
+    assert_eq!(unliked.json()["already_liked"], false);
+
+    let likes = app.get(&format!("/users/{}/posts", user_id), None).await;
+    assert_eq!(likes.json(), json!([]));
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn also_liked_is_loaded_for_every_post_at_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let store = &app.store;
+    let mut post_ids = Vec::new();
+    for day in 1..=3 {
+        let post = store
+            .add_post(CreatePost {
+                title: format!("Post {}", day),
+                query_string: format!("2023-05-0{}", day),
+                explanation: String::new(),
+                img_url: format!("https://example.com/{}.jpg", day),
+                apod_date: format!("2023-05-0{}", day),
+            })
+            .await
+            .unwrap();
+        post_ids.push(post.id);
+    }
+    for (email, liked) in [
+        ("ada@example.com", &post_ids[..]),
+        ("bob@example.com", &post_ids[..2]),
+    ] {
+        app.register(email, "hunter22").await;
+        let user_id = app.user_id(email).await;
+        for post_id in liked {
+            store
+                .create_vote(CreateVote {
+                    post_id: *post_id,
+                    user_id,
+                })
+                .await
+                .unwrap();
+        }
+    }
+    store.refresh_post_similarities().await.unwrap();
+
+    let ids: Vec<i32> = post_ids.iter().map(|id| id.0).collect();
+    let mut also_liked = store.get_similar_posts_for_posts(&ids, 1).await.unwrap();
+    for id in ids {
+        let one_at_a_time = store.get_similar_posts(id, 1).await.unwrap();
+        let batched = also_liked.remove(&id).unwrap_or_default();
+        let titles = |posts: &[SimilarPost]| -> Vec<String> {
+            posts.iter().map(|post| post.title.clone()).collect()
+        };
+        assert_eq!(titles(&batched), titles(&one_at_a_time), "post {}", id);
+        assert_eq!(batched.len(), 1);
+    }
+}
+
+// Live updates --------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn live_events_stream_new_posts_and_votes_until_shutdown(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("ada@example.com", "hunter22").await;
+    let events = app
+        .open(Request::get("/events").body(Body::empty()).unwrap())
+        .await;
+    assert_eq!(events.status(), StatusCode::OK);
+    assert_eq!(events.headers()[CONTENT_TYPE], "text/event-stream");
+
+    app.post_form(
+        "/get_apod",
+        &[("query_string", "2023-05-01")],
+        Some(&session),
+    )
+    .await;
+    let post_id = app.get("/posts", None).await.json()[0]["id"].clone();
+    app.post_json("/votes/json", json!({ "post_id": post_id }), Some(&session))
+        .await;
+
+    let mut events = events.into_body();
+    let mut body = String::new();
+    while !body.contains("event:vote_count") {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), events.data())
+            .await
+            .expect("no live event within five seconds")
+            .unwrap()
+            .unwrap();
+        body.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(body.contains("event:new_post\n"), "{}", body);
+    assert!(body.contains("Fake APOD for 2023-05-01"), "{}", body);
+    assert!(
+        body.contains(&format!(
+            "event:vote_count\ndata:{{\"post_id\":{},\"num_likes\":1}}",
+            post_id
+        )),
+        "{}",
+        body
+    );
+
+    // Open streams end once the server starts shutting down, rather than holding it open
+    app.shutdown.cancel();
+    let end = tokio::time::timeout(std::time::Duration::from_secs(5), events.data()).await;
+    assert!(matches!(end, Ok(None)), "{:?}", end);
+}
+
+// Feeds ---------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn feeds_link_to_the_public_base_url(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("ada@example.com", "hunter22").await;
+    app.post_form(
+        "/get_apod",
+        &[("query_string", "2023-05-01")],
+        Some(&session),
+    )
+    .await;
+    let post_id = app.get("/posts", None).await.json()[0]["id"].clone();
+
+    // Whatever Host the client claims, links use PUBLIC_BASE_URL
+    let request = Request::get("/feed.rss")
+        .header("host", "evil.example")
+        .body(Body::empty())
+        .unwrap();
+    let rss = app.send(request).await;
+    assert_eq!(rss.status, StatusCode::OK);
+    assert!(!rss.body.contains("evil.example"), "{}", rss.body);
+    assert!(rss.body.contains(&format!(
+        "<link>https://astro.test/#post-{}</link>",
+        post_id
+    )));
+}
+
+// Follows -------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn following_yourself_is_refused(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("ada@example.com", "hunter22").await;
+
+    let response = app
+        .post_form("/follow", &[("email", "ada@example.com")], Some(&session))
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT, "{}", response.body);
+    assert_eq!(response.json()["code"], "cannot_act_on_yourself");
+}
+
+// Notifications -------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn notifications_are_only_read_by_their_owner(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let ada = app.register("ada@example.com", "hunter22").await;
+    let bob = app.register("bob@example.com", "hunter22").await;
+    let carol = app.register("carol@example.com", "hunter22").await;
+    for session in [&bob, &carol] {
+        app.post_form("/follow", &[("email", "ada@example.com")], Some(session))
+            .await;
+    }
+    let ada_id = app.user_id("ada@example.com").await;
+    let notifications = app.store.get_notifications_for_user(ada_id).await.unwrap();
+    assert_eq!(notifications.len(), 2);
+    let unread = app.get("/notifications/unread", Some(&ada)).await;
+    assert_eq!(unread.json()["unread_count"], 2);
+
+    let page = app.get("/notifications", Some(&ada)).await;
+    assert!(page.body.contains("bob@example.com started following you"));
+
+    // Bob can't see ada's notifications or mark one read, even by id
+    let page = app.get("/notifications", Some(&bob)).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(
+        !page.body.contains("started following you"),
+        "{}",
+        page.body
+    );
+    let id = notifications[0].id.0.to_string();
+    app.post_form("/notifications/read", &[("id", &id)], Some(&bob))
+        .await;
+    app.post_form("/notifications/read_all", &[], Some(&bob))
+        .await;
+    let unread = app.get("/notifications/unread", Some(&ada)).await;
+    assert_eq!(unread.json()["unread_count"], 2);
+
+    let read = app
+        .post_form("/notifications/read", &[("id", &id)], Some(&ada))
+        .await;
+    assert_eq!(read.status, StatusCode::FOUND, "{}", read.body);
+    assert_eq!(read.location(), Some("/notifications"));
+    let unread = app.get("/notifications/unread", Some(&ada)).await;
+    assert_eq!(unread.json()["unread_count"], 1);
+    app.post_form("/notifications/read_all", &[], Some(&ada))
+        .await;
+    let unread = app.get("/notifications/unread", Some(&ada)).await;
+    assert_eq!(unread.json()["unread_count"], 0);
+
+    let page = app.get("/notifications", None).await;
+    assert_eq!(page.status, StatusCode::UNAUTHORIZED);
+}
+
+// Admin ---------------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn the_dashboard_is_for_admins(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let admin = app.user_with_role("admin@example.com", Role::Admin).await;
+    let moderator = app
+        .user_with_role("moderator@example.com", Role::Moderator)
+        .await;
+    let user = app.register("ada@example.com", "hunter22").await;
+
+    for path in ["/dashboard", "/dashboard/json"] {
+        let response = app.get(path, None).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{}", path);
+        for session in [&user, &moderator] {
+            let response = app.get(path, Some(session)).await;
+            assert_eq!(response.status, StatusCode::FORBIDDEN, "{}", path);
+            assert_eq!(response.json()["code"], "missing_permission");
+        }
+        let response = app.get(path, Some(&admin)).await;
+        assert_eq!(
+            response.status,
+            StatusCode::OK,
+            "{}: {}",
+            path,
+            response.body
+        );
+    }
+
+    let stats = app.get("/dashboard/json", Some(&admin)).await.json();
+    assert_eq!(stats["total_users"], 3);
+    assert_eq!(stats["admins"], 1);
+    let days = stats["daily_signups"].as_array().unwrap();
+    assert_eq!(days.len(), STATS_DAYS as usize);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn ban_shows_the_banned_page(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let admin = app.user_with_role("admin@example.com", Role::Admin).await;
+    let user = app.register("ada@example.com", "hunter22").await;
+
+    let response = app
+        .post_form(
+            "/ban",
+            &[
+                ("email", "ada@example.com"),
+                ("reason", "Spam"),
+                ("expires_on", ""),
+            ],
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+
+    let home = app.get("/", Some(&user)).await;
+    assert!(home.body.contains("You have been banned"));
+    assert!(home.body.contains("Spam"));
+
+    // The audit entry and the notification go in with the ban
+    let audit = app.get("/audit/export", Some(&admin)).await.json();
+    let bans: Vec<_> = audit
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["action"] == "ban_user")
+        .collect();
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0]["actor_email"], "admin@example.com");
+    assert_eq!(bans[0]["target"], "ada@example.com");
+    assert_eq!(bans[0]["payload"]["reason"], "Spam");
+    let user_id = app.user_id("ada@example.com").await;
+    let notifications = app.store.get_notifications_for_user(user_id).await.unwrap();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].kind, "banned");
+
+    // Regular users can't ban anyone
+    let response = app
+        .post_form("/ban", &[("email", "admin@example.com")], Some(&user))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn unbanning_leaves_expired_bans_alone(pool: PgPool) {
+    let app = TestApp::new(pool.clone()).await;
+    app.register("bob@example.com", "hunter22").await;
+    let bob_id = app.user_id("bob@example.com").await;
+    sqlx::query("INSERT INTO bans (user_id, reason, expires_on) VALUES ($1, 'Spam', $2)")
+        .bind(bob_id)
+        .bind(Utc::now() - Duration::days(1))
+        .execute(&pool)
+        .await
+        .unwrap();
+    app.repo
+        .ban_user_by_email(
+            "admin-cli".to_string(),
+            None,
+            "bob@example.com".to_string(),
+            "Spam".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    app.repo
+        .unban_user_by_email("admin-cli".to_string(), None, "bob@example.com".to_string())
+        .await
+        .unwrap();
+    let mut bans = app
+        .store
+        .get_ban_history_by_email("bob@example.com".to_string())
+        .await
+        .unwrap();
+    bans.sort_by_key(|ban| ban.expires_on.is_none());
+    assert_eq!(bans.len(), 2);
+    assert!(bans[0].lifted_on.is_none(), "{:?}", bans[0]);
+    assert!(bans[1].lifted_on.is_some(), "{:?}", bans[1]);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn promote_needs_an_owner(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let owner = app.user_with_role("owner@example.com", Role::Owner).await;
+    let admin = app.user_with_role("admin@example.com", Role::Admin).await;
+    app.register("ada@example.com", "hunter22").await;
+    let promote = [("email", "ada@example.com")];
+
+    let response = app.post_form("/promote", &promote, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app.post_form("/promote", &promote, Some(&owner)).await;
+    assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+    let role = app
+        .store
+        .get_user_role_by_email("ada@example.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(role, Role::Admin);
+
+    let response = app.post_form("/promote", &promote, Some(&owner)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn demoting_the_last_two_admins_at_once_keeps_one(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.user_with_role("ada@example.com", Role::Admin).await;
+    app.user_with_role("bob@example.com", Role::Admin).await;
+
+    let (ada, bob) = tokio::join!(
+        app.repo.demote_admin_by_email(
+            "bob@example.com".to_string(),
+            None,
+            "ada@example.com".to_string()
+        ),
+        app.repo.demote_admin_by_email(
+            "ada@example.com".to_string(),
+            None,
+            "bob@example.com".to_string()
+        ),
+    );
+
+    let mut results = [ada, bob];
+    results.sort_by_key(|result| result.is_err());
+    assert!(results[0].is_ok());
+    assert!(
+        matches!(results[1], Err(AppError::LastAdmin)),
+        "{:?}",
+        results[1]
+    );
+    assert_eq!(app.store.get_admins().await.unwrap().len(), 1);
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn single_actions_check_rank_again_under_the_lock(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.user_with_role("ada@example.com", Role::Admin).await;
+    app.register("bob@example.com", "hunter22").await;
+
+    // Bob is promoted after ada's handler checked his rank but before her write takes the lock
+    app.repo
+        .set_user_role_by_email(
+            "admin-cli".to_string(),
+            None,
+            "bob@example.com".to_string(),
+            Role::Admin,
+        )
+        .await
+        .unwrap();
+    let result = app
+        .repo
+        .ban_user_by_email(
+            "ada@example.com".to_string(),
+            Some(Role::Admin),
+            "bob@example.com".to_string(),
+            "Spam".to_string(),
+            None,
+        )
+        .await;
+    assert!(
+        matches!(result, Err(AppError::CannotActOnUser)),
+        "{:?}",
+        result
+    );
+    let result = app
+        .repo
+        .demote_admin_by_email(
+            "ada@example.com".to_string(),
+            Some(Role::Admin),
+            "bob@example.com".to_string(),
+        )
+        .await;
+    assert!(
+        matches!(result, Err(AppError::CannotActOnUser)),
+        "{:?}",
+        result
+    );
+    assert_eq!(app.store.get_admins().await.unwrap().len(), 2);
+}
+
+// Moderation ----------------------------------------------------------------------------------------------------------
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn a_report_is_only_resolved_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.user_with_role("ada@example.com", Role::Admin).await;
+    app.user_with_role("bob@example.com", Role::Admin).await;
+    app.register("spammer@example.com", "hunter22").await;
+    let reporter_id = app.user_id("ada@example.com").await;
+    let spammer_id = app.user_id("spammer@example.com").await;
+
+    let store = &app.store;
+    store
+        .create_report(
+            reporter_id,
+            ReportTarget::User,
+            spammer_id,
+            "Spam".to_string(),
+        )
+        .await
+        .unwrap();
+    let report = store.get_open_reports().await.unwrap().remove(0);
+
+    let (ada, bob) = tokio::join!(
+        store.resolve_report(
+            "ada@example.com".to_string(),
+            Role::Admin,
+            &report,
+            ReportResolution::AuthorBanned,
+            String::new(),
+        ),
+        store.resolve_report(
+            "bob@example.com".to_string(),
+            Role::Admin,
+            &report,
+            ReportResolution::AuthorBanned,
+            String::new(),
+        ),
+    );
+
+    let mut results = [ada, bob];
+    results.sort_by_key(|result| result.is_err());
+    assert!(results[0].is_ok());
+    assert!(
+        matches!(results[1], Err(AppError::ReportAlreadyResolved)),
+        "{:?}",
+        results[1]
+    );
+    let bans = store
+        .get_ban_history_by_email("spammer@example.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].reason, "Spam");
+    assert!(store.get_open_reports().await.unwrap().is_empty());
+}
+
+#[sqlx::test(migrator = "backend::db::MIGRATOR")]
+async fn bulk_actions_check_rank_and_audit_every_user(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let admin = app.user_with_role("admin@example.com", Role::Admin).await;
+    app.register("ada@example.com", "hunter22").await;
+    app.register("bob@example.com", "hunter22").await;
+
+    let response = app
+        .post_form(
+            "/users/bulk",
+            &[
+                ("action", "ban"),
+                ("email", "ada@example.com"),
+                ("email", "bob@example.com"),
+                ("reason", "Spam"),
+                ("confirm", "yes"),
+            ],
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+
+    let audit = app.get("/audit/export", Some(&admin)).await.json();
+    let mut banned: Vec<_> = audit
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["action"] == "ban_user")
+        .map(|entry| entry["target"].as_str().unwrap().to_string())
+        .collect();
+    banned.sort();
+    assert_eq!(banned, ["ada@example.com", "bob@example.com"]);
+
+    // The rank check is made again under the row locks, so someone promoted after the
+    // confirmation page was shown is refused, and nobody in the batch is touched
+    app.store
+        .set_user_role_by_email(
+            "admin-cli".to_string(),
+            None,
+            "bob@example.com".to_string(),
+            Role::Admin,
+        )
+        .await
+        .unwrap();
+    let bulk = BulkUserAction {
+        action: BulkAction::Unban,
+        emails: vec!["ada@example.com".to_string(), "bob@example.com".to_string()],
+        reason: String::new(),
+        confirmed: true,
+    };
+    let result = app
+        .store
+        .bulk_update_users("admin@example.com".to_string(), &bulk)
+        .await;
+    assert!(
+        matches!(result, Err(AppError::CannotActOnUser)),
+        "{:?}",
+        result
+    );
+    let ada_banned = app
+        .store
+        .determine_if_user_banned("ada@example.com".to_string())
+        .await
+        .unwrap();
+    assert!(ada_banned);
+}
+
+// In memory -----------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn admin_actions_run_on_the_in_memory_repository() {
+    let repo = Arc::new(InMemoryRepository::new());
+    let app = TestApp::in_memory(repo.clone()).await;
+    let owner = app.user_with_role("owner@example.com", Role::Owner).await;
+    app.register("ada@example.com", "hunter22").await;
+    app.register("bob@example.com", "hunter22").await;
+
+    // A duplicate signup is a conflict, the same as the unique constraint gives on Postgres
+    let duplicate = repo
+        .create_user(UserSignup {
+            email: "ada@example.com".to_string(),
+            password: "hashed".to_string(),
+            confirm_password: "hashed".to_string(),
+        })
+        .await;
+    assert!(
+        matches!(duplicate, Err(AppError::Conflict(_))),
+        "{:?}",
+        duplicate
+    );
+
+    let promoted = app
+        .post_form("/promote", &[("email", "ada@example.com")], Some(&owner))
+        .await;
+    assert_eq!(promoted.status, StatusCode::FOUND, "{}", promoted.body);
+    let banned = app
+        .post_form(
+            "/ban",
+            &[
+                ("email", "bob@example.com"),
+                ("reason", "Spam"),
+                ("expires_on", ""),
+            ],
+            Some(&owner),
+        )
+        .await;
+    assert_eq!(banned.status, StatusCode::FOUND, "{}", banned.body);
+    assert!(repo
+        .get_active_ban_by_email("bob@example.com".to_string())
+        .await
+        .unwrap()
+        .is_some());
+
+    let unbanned = app
+        .post_form(
+            "/users/bulk",
+            &[
+                ("action", "unban"),
+                ("email", "bob@example.com"),
+                ("confirm", "yes"),
+            ],
+            Some(&owner),
+        )
+        .await;
+    assert_eq!(unbanned.status, StatusCode::FOUND, "{}", unbanned.body);
+    assert!(repo
+        .get_active_ban_by_email("bob@example.com".to_string())
+        .await
+        .unwrap()
+        .is_none());
+
+    let actions: Vec<_> = repo
+        .audit_log()
+        .into_iter()
+        .filter(|entry| entry.actor_email == "owner@example.com")
+        .map(|entry| entry.action)
+        .collect();
+    assert_eq!(actions, ["promote_admin", "ban_user", "unban_user"]);
+}